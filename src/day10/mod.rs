@@ -8,4 +8,26 @@ pub fn part1() {
 pub fn part2() {
     let input = std::str::from_utf8(include_bytes!("../../resource/day10/input1")).unwrap();
     println!("{}", pipe_maze::enclosed_tiles(input))
-}
\ No newline at end of file
+}
+
+pub fn part2_squeezed() {
+    let input = std::str::from_utf8(include_bytes!("../../resource/day10/input1")).unwrap();
+    println!("{}", pipe_maze::squeezed_enclosed_tiles(input))
+}
+
+pub fn render() {
+    let input = std::str::from_utf8(include_bytes!("../../resource/day10/input1")).unwrap();
+    println!("{}", pipe_maze::render_maze(input, true))
+}
+
+pub fn render_to_file(path: &str) {
+    let input = std::str::from_utf8(include_bytes!("../../resource/day10/input1")).unwrap();
+    std::fs::write(path, pipe_maze::render_maze(input, false)).expect("Unable to write rendered maze")
+}
+
+pub fn validate() {
+    let input = std::str::from_utf8(include_bytes!("../../resource/day10/input1")).unwrap();
+    for issue in pipe_maze::validate_maze(input) {
+        println!("{}", issue)
+    }
+}
@@ -1,9 +1,34 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashSet, VecDeque};
 use std::fmt::{Display, Formatter, Write};
 use std::str::FromStr;
 
 struct MainLoop {}
 
+/// Tiles that are not part of the main loop, split by whether the loop encloses them.
+struct Enclosure {
+    inside: HashSet<(usize, usize)>,
+    outside: HashSet<(usize, usize)>,
+}
+
+/// How tiles are decided to be enclosed by the main loop. `Parity` sweeps rows across the loop,
+/// whereas `Squeeze` floods the maze from the outside, letting the animal squeeze between pipes
+/// that are adjacent but not connected to each other.
+#[derive(Debug, Clone, Copy)]
+enum EnclosureMode {
+    Parity,
+    Squeeze,
+}
+
+/// Problems found while validating a maze, all located by `(row, column)`.
+#[derive(Debug, PartialEq)]
+enum MazeIssue {
+    MissingStart,
+    DanglingEnd { position: (usize, usize), direction: usize },
+    DisconnectedStart { position: (usize, usize), connections: usize },
+    AmbiguousStart { position: (usize, usize), closing_tiles: Vec<Tile> },
+    LoopBreak { position: (usize, usize), from: usize },
+}
+
 struct Maze {
     width: usize,
     height: usize,
@@ -11,7 +36,7 @@ struct Maze {
     start_position: (usize, usize),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Tile {
     Air,
     Start,
@@ -30,20 +55,26 @@ pub fn farthest_point_in_loop(input: &str) -> u32 {
 
 pub fn enclosed_tiles(input: &str) -> u32 {
     let maze = parse_maze(input);
-    let main_loop = maze.calculate_main_loop();
-    maze.enclosed_tiles(main_loop).len() as u32
+    maze.enclosure(EnclosureMode::Parity).inside.len() as u32
+}
+
+pub fn render_maze(input: &str, colored: bool) -> String {
+    let maze = parse_maze(input);
+    maze.render(colored)
+}
+
+pub fn squeezed_enclosed_tiles(input: &str) -> u32 {
+    let maze = parse_maze(input);
+    maze.enclosure(EnclosureMode::Squeeze).inside.len() as u32
 }
 
 fn parse_maze(input: &str) -> Maze {
-    let mut tiles = input
-        .lines()
-        .map(|line| line.chars().map(Tile::from).collect::<Vec<_>>())
-        .collect::<Vec<_>>();
+    let mut tiles = parse_tiles(input);
 
     let height = tiles.len();
     let width = tiles[0].len();
 
-    let start_position = find_start_position(&tiles);
+    let start_position = find_start_position(&tiles).expect("Start position must be found");
     let start_tile = interpolate_tile_at_start_position(&tiles, start_position);
 
     tiles[start_position.0][start_position.1] = start_tile;
@@ -56,7 +87,14 @@ fn parse_maze(input: &str) -> Maze {
     }
 }
 
-fn find_start_position(tiles: &[Vec<Tile>]) -> (usize, usize) {
+fn parse_tiles(input: &str) -> Vec<Vec<Tile>> {
+    input
+        .lines()
+        .map(|line| line.chars().map(Tile::from).collect::<Vec<_>>())
+        .collect::<Vec<_>>()
+}
+
+fn find_start_position(tiles: &[Vec<Tile>]) -> Option<(usize, usize)> {
     tiles
         .iter().enumerate()
         .find_map(|(row, rows)| rows.iter().enumerate()
@@ -65,7 +103,7 @@ fn find_start_position(tiles: &[Vec<Tile>]) -> (usize, usize) {
             } else {
                 None
             })
-        ).expect("Start position must be found")
+        )
 }
 
 const NORTH: usize = 1;
@@ -73,41 +111,117 @@ const SOUTH: usize = 2;
 const EAST: usize = 4;
 const WEST: usize = 8;
 
-fn interpolate_tile_at_start_position(tiles: &[Vec<Tile>], (row, column): (usize, usize)) -> Tile {
+fn interpolate_tile_at_start_position(tiles: &[Vec<Tile>], position: (usize, usize)) -> Tile {
+    let connections = start_connections(tiles, position);
+    Tile::from_connections(connections)
+        .unwrap_or_else(|| panic!("Start position does not connect to exactly 2 neighbors. [{connections}]"))
+}
+
+/// The directions in which the neighbours of the start position connect back to it.
+fn start_connections(tiles: &[Vec<Tile>], (row, column): (usize, usize)) -> usize {
     let mut connections = 0;
-    if row > 0 {
-        let top_neighbor = &tiles[row - 1][column];
-        if [Tile::Vertical, Tile::SouthWest, Tile::SouthEast].contains(top_neighbor) {
-            connections |= NORTH;
-        }
+    if row > 0 && tiles[row - 1][column].connections() & SOUTH != 0 {
+        connections |= NORTH;
     }
-    if column > 0 {
-        let left_neighbor = &tiles[row][column - 1];
-        if [Tile::Horizontal, Tile::SouthEast, Tile::NorthEast].contains(left_neighbor) {
-            connections |= WEST;
-        }
+    if column > 0 && tiles[row][column - 1].connections() & EAST != 0 {
+        connections |= WEST;
     }
-    if row < tiles.len() - 1 {
-        let bottom_neighbor = &tiles[row + 1][column];
-        if [Tile::Vertical, Tile::NorthEast, Tile::NorthWest].contains(bottom_neighbor) {
-            connections |= SOUTH;
+    if row < tiles.len() - 1 && tiles[row + 1][column].connections() & NORTH != 0 {
+        connections |= SOUTH;
+    }
+    if column < tiles[row].len() - 1 && tiles[row][column + 1].connections() & WEST != 0 {
+        connections |= EAST;
+    }
+    connections
+}
+
+/// Checks the maze for pipe ends that do not connect to anything, for a start position that does
+/// not connect to exactly two neighbours and for breaks in the main loop. If the start connects to
+/// more than two neighbours, the start tiles that would close the loop are suggested.
+pub fn validate_maze(input: &str) -> Vec<String> {
+    find_maze_issues(input).iter().map(|issue| issue.to_string()).collect()
+}
+
+fn find_maze_issues(input: &str) -> Vec<MazeIssue> {
+    let tiles = parse_tiles(input);
+    let height = tiles.len();
+    let width = tiles.first().map(|row| row.len()).unwrap_or(0);
+    let mut issues = Vec::new();
+
+    for (row, tiles_in_row) in tiles.iter().enumerate() {
+        for (col, tile) in tiles_in_row.iter().enumerate() {
+            for direction in [NORTH, SOUTH, EAST, WEST].into_iter().filter(|direction| tile.connections() & direction != 0) {
+                let connected = match direction {
+                    NORTH if row == 0 => false,
+                    SOUTH if row + 1 == height => false,
+                    WEST if col == 0 => false,
+                    EAST if col + 1 >= tiles_in_row.len() => false,
+                    _ => {
+                        let (next_row, next_col) = step_towards((row, col), direction);
+                        let neighbour = tiles[next_row].get(next_col).unwrap_or(&Tile::Air);
+                        neighbour == &Tile::Start || neighbour.connections() & opposite(direction) != 0
+                    }
+                };
+                if !connected {
+                    issues.push(MazeIssue::DanglingEnd { position: (row, col), direction });
+                }
+            }
         }
     }
-    if column < tiles[0].len() - 1 {
-        let right_neighbor = &tiles[row][column + 1];
-        if [Tile::Horizontal, Tile::SouthWest, Tile::NorthWest].contains(right_neighbor) {
-            connections |= EAST;
+
+    let Some(start_position) = find_start_position(&tiles) else {
+        issues.push(MazeIssue::MissingStart);
+        return issues;
+    };
+    let connections = start_connections(&tiles, start_position);
+    let maze_with_start = |start_tile: Tile| {
+        let mut tiles = tiles.clone();
+        tiles[start_position.0][start_position.1] = start_tile;
+        Maze { width, height, tiles, start_position }
+    };
+
+    match connections.count_ones() {
+        0 | 1 => issues.push(MazeIssue::DisconnectedStart { position: start_position, connections: connections.count_ones() as usize }),
+        2 => if let Err(loop_break) = maze_with_start(Tile::from_connections(connections).unwrap()).trace_main_loop() {
+            issues.push(loop_break);
+        }
+        _ => {
+            let closing_tiles = [Tile::Vertical, Tile::Horizontal, Tile::NorthEast, Tile::NorthWest, Tile::SouthEast, Tile::SouthWest]
+                .into_iter()
+                .filter(|tile| tile.connections() & connections == tile.connections())
+                .filter(|&tile| maze_with_start(tile).trace_main_loop().is_ok())
+                .collect();
+            issues.push(MazeIssue::AmbiguousStart { position: start_position, closing_tiles });
         }
     }
+    issues
+}
+
+fn direction_name(direction: usize) -> &'static str {
+    match direction {
+        NORTH => "north",
+        SOUTH => "south",
+        EAST => "east",
+        WEST => "west",
+        _ => "unknown"
+    }
+}
 
-    match connections {
-        c if c == (NORTH | SOUTH) => Tile::Vertical,
-        c if c == (EAST | WEST) => Tile::Horizontal,
-        c if c == (NORTH | EAST) => Tile::NorthEast,
-        c if c == (NORTH | WEST) => Tile::NorthWest,
-        c if c == (SOUTH | EAST) => Tile::SouthEast,
-        c if c == (SOUTH | WEST) => Tile::SouthWest,
-        _ => panic!("Start position does not connect to exactly 2 neighbors. [{connections}]")
+impl Display for MazeIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MazeIssue::MissingStart => write!(f, "No start position found"),
+            MazeIssue::DanglingEnd { position: (row, col), direction } =>
+                write!(f, "({row}, {col}) pipe end towards {} is not connected", direction_name(*direction)),
+            MazeIssue::DisconnectedStart { position: (row, col), connections } =>
+                write!(f, "({row}, {col}) start connects to {connections} neighbors instead of 2"),
+            MazeIssue::AmbiguousStart { position: (row, col), closing_tiles } => {
+                let tiles = closing_tiles.iter().map(|tile| tile.to_string()).collect::<Vec<_>>().join(", ");
+                write!(f, "({row}, {col}) start connects to more than 2 neighbors, tiles closing the loop: [{tiles}]")
+            }
+            MazeIssue::LoopBreak { position: (row, col), from } =>
+                write!(f, "({row}, {col}) loop breaks after entering from {}", direction_name(*from)),
+        }
     }
 }
 
@@ -157,207 +271,284 @@ impl Display for Maze {
     }
 }
 
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_LOOP: &str = "\x1b[1;33m";
+const ANSI_START: &str = "\x1b[1;42;30m";
+const ANSI_FARTHEST: &str = "\x1b[1;41;30m";
+const ANSI_INSIDE: &str = "\x1b[32m";
+const ANSI_OUTSIDE: &str = "\x1b[2;34m";
+
 impl Maze {
-    fn farthest_distance_in_loop(&self) -> u32 {
-        let (start_row, start_col) = self.start_position;
-        let start_tile = &self.tiles[start_row][start_col];
-        let mut distances = HashMap::new();
-        distances.insert((start_row, start_col), 0);
-
-        let mut traverse_queue: Vec<((usize, usize), usize, u32)> = Vec::new();
-        match start_tile {
-            Tile::Vertical => {
-                traverse_queue.push(((start_row - 1, start_col), SOUTH, 0));
-                traverse_queue.push(((start_row + 1, start_col), NORTH, 0));
-            }
-            Tile::Horizontal => {
-                traverse_queue.push(((start_row, start_col - 1), EAST, 0));
-                traverse_queue.push(((start_row, start_col + 1), WEST, 0));
-            }
-            Tile::NorthEast => {
-                traverse_queue.push(((start_row - 1, start_col), SOUTH, 0));
-                traverse_queue.push(((start_row, start_col + 1), WEST, 0));
-            }
-            Tile::NorthWest => {
-                traverse_queue.push(((start_row - 1, start_col), SOUTH, 0));
-                traverse_queue.push(((start_row, start_col - 1), EAST, 0));
-            }
-            Tile::SouthEast => {
-                traverse_queue.push(((start_row + 1, start_col), NORTH, 0));
-                traverse_queue.push(((start_row, start_col + 1), WEST, 0));
-            }
-            Tile::SouthWest => {
-                traverse_queue.push(((start_row + 1, start_col), NORTH, 0));
-                traverse_queue.push(((start_row, start_col - 1), EAST, 0));
+    /// Renders the maze with box-drawing glyphs. The start is marked with `S`, the point of the
+    /// main loop farthest from it with `*` and enclosed tiles with `I`. With `colored` set, ANSI
+    /// colors additionally highlight the main loop and tell enclosed and outside tiles apart,
+    /// otherwise the output is plain text suitable for writing to a file.
+    fn render(&self, colored: bool) -> String {
+        let main_loop = self.main_loop().collect::<Vec<_>>();
+        let farthest_point = main_loop[main_loop.len() / 2].position;
+        let loop_positions = main_loop.iter().map(|loop_step| loop_step.position).collect::<HashSet<_>>();
+        let enclosure = self.classify_tiles();
+
+        let mut rendered = String::new();
+        for (row, tiles) in self.tiles.iter().enumerate() {
+            for (col, tile) in tiles.iter().enumerate() {
+                let position = (row, col);
+                let (glyph, color) = if position == self.start_position {
+                    ('S', ANSI_START)
+                } else if position == farthest_point {
+                    ('*', ANSI_FARTHEST)
+                } else if loop_positions.contains(&position) {
+                    (tile.box_glyph(), ANSI_LOOP)
+                } else if enclosure.inside.contains(&position) {
+                    ('I', ANSI_INSIDE)
+                } else {
+                    (tile.box_glyph(), ANSI_OUTSIDE)
+                };
+
+                if colored {
+                    write!(rendered, "{color}{glyph}{ANSI_RESET}").unwrap();
+                } else {
+                    rendered.push(glyph);
+                }
             }
-            _ => panic!("Invalid start tile {start_tile}")
+            rendered.push('\n');
         }
+        rendered
+    }
+}
 
-        while let Some((next_position, from, distance_from_start)) = traverse_queue.pop() {
-            self.traverse(next_position, from, distance_from_start, &mut traverse_queue, |(row, col), dst, _| match distances.get_mut(&(row, col)) {
-                Some(distance) if distance_from_start < *distance => {
-                    *distance = distance_from_start;
-                    true
-                },
-                Some(_) => false,
-                None => {
-                    distances.insert((row, col), distance_from_start);
-                    true
-                }
-            });
+/// A tile of the main loop. `from` is the side through which the tile was entered, and `step` is
+/// the number of steps taken from the start position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LoopStep {
+    position: (usize, usize),
+    from: usize,
+    step: usize,
+}
+
+/// Walks the main loop once, beginning at the start position and stopping right before returning to it.
+struct LoopIter<'a> {
+    maze: &'a Maze,
+    next: Option<LoopStep>,
+}
+
+impl LoopIter<'_> {
+    /// Resolves the step following `current`, or `None` once the loop is back at the start.
+    fn advance(&self, current: LoopStep) -> Result<Option<LoopStep>, MazeIssue> {
+        let (row, col) = current.position;
+        let tile = &self.maze.tiles[row][col];
+        let exit = tile.connections() & !current.from;
+        let loop_break = MazeIssue::LoopBreak { position: current.position, from: current.from };
+        if tile.connections() & current.from == 0 || exit.count_ones() != 1 {
+            return Err(loop_break);
         }
 
-        let mut distance_values = distances.values().collect::<Vec<_>>();
-        distance_values.sort();
-        **distance_values.last().unwrap()
+        let position = self.maze.neighbour(current.position, exit).ok_or(loop_break)?;
+        if position == self.maze.start_position {
+            let (start_row, start_col) = self.maze.start_position;
+            // the loop only closes if it enters the start through its other connection
+            if self.maze.tiles[start_row][start_col].connections() & opposite(exit) == 0 {
+                return Err(MazeIssue::LoopBreak { position, from: opposite(exit) });
+            }
+            return Ok(None);
+        }
+        Ok(Some(LoopStep { position, from: opposite(exit), step: current.step + 1 }))
     }
+}
 
-    fn traverse<F: FnMut((usize, usize), u32, usize) -> bool>(&self, (row, col): (usize, usize), from: usize, distance_from_start: u32, queue: &mut Vec<((usize, usize), usize, u32)>, mut traverse_fn: F) {
-        let distance_from_start = distance_from_start + 1;
-        if !traverse_fn((row, col), distance_from_start, from) {
-            return
-        }
+impl<'a> Iterator for LoopIter<'a> {
+    type Item = LoopStep;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next?;
+        self.next = self.advance(current).unwrap_or_else(|issue| panic!("{issue}"));
+        Some(current)
+    }
+}
 
-        let (next_position, from) = match (&self.tiles[row][col], from) {
-            (Tile::Horizontal, EAST) => ((row, col - 1), EAST),
-            (Tile::Horizontal, WEST) => ((row, col + 1), WEST),
-            (Tile::Vertical, SOUTH) => ((row - 1, col), SOUTH),
-            (Tile::Vertical, NORTH) => ((row + 1, col), NORTH),
-            (Tile::NorthEast, NORTH) => ((row, col + 1), WEST),
-            (Tile::NorthEast, EAST) => ((row - 1, col), SOUTH),
-            (Tile::NorthWest, NORTH) => ((row, col - 1), EAST),
-            (Tile::NorthWest, WEST) => ((row - 1, col), SOUTH),
-            (Tile::SouthEast, SOUTH) => ((row, col + 1), WEST),
-            (Tile::SouthEast, EAST) => ((row + 1, col), NORTH),
-            (Tile::SouthWest, SOUTH) => ((row, col - 1), EAST),
-            (Tile::SouthWest, WEST) => ((row + 1, col), NORTH),
-            (tile, from) => panic!("({row}, {col}) {tile} - {from}")
-        };
-
-        queue.push((next_position, from, distance_from_start));
+fn step_towards((row, col): (usize, usize), direction: usize) -> (usize, usize) {
+    match direction {
+        NORTH => (row - 1, col),
+        SOUTH => (row + 1, col),
+        EAST => (row, col + 1),
+        WEST => (row, col - 1),
+        _ => panic!("Invalid direction {direction}")
     }
+}
 
-    fn calculate_main_loop(&self) -> HashSet<(usize, usize)> {
-        let (start_row, start_col) = self.start_position;
-        let start_tile = &self.tiles[start_row][start_col];
-        let mut traverse_queue = Vec::new();
-
-        match start_tile {
-            Tile::Vertical => traverse_queue.push(((start_row - 1, start_col), SOUTH, 0)),
-            Tile::Horizontal => traverse_queue.push(((start_row, start_col - 1), EAST, 0)),
-            Tile::NorthEast => traverse_queue.push(((start_row - 1, start_col), SOUTH, 0)),
-            Tile::NorthWest => traverse_queue.push(((start_row - 1, start_col), SOUTH, 0)),
-            Tile::SouthEast => traverse_queue.push(((start_row + 1, start_col), NORTH, 0)),
-            Tile::SouthWest => traverse_queue.push(((start_row + 1, start_col), NORTH, 0)),
-            _ => panic!("Invalid start tile {start_tile}")
-        };
-
-        let mut main_loop = HashSet::new();
-
-        while let Some((next_position, from, distance_from_start)) = traverse_queue.pop() {
-            self.traverse(next_position, from, distance_from_start, &mut traverse_queue, |pos, _, _| main_loop.insert(pos));
+fn opposite(direction: usize) -> usize {
+    match direction {
+        NORTH => SOUTH,
+        SOUTH => NORTH,
+        EAST => WEST,
+        WEST => EAST,
+        _ => panic!("Invalid direction {direction}")
+    }
+}
+
+impl Maze {
+    fn neighbour(&self, (row, col): (usize, usize), direction: usize) -> Option<(usize, usize)> {
+        match direction {
+            NORTH if row == 0 => None,
+            SOUTH if row + 1 == self.height => None,
+            WEST if col == 0 => None,
+            EAST if col + 1 == self.width => None,
+            _ => Some(step_towards((row, col), direction))
         }
+    }
 
-        main_loop
+    /// Walks the main loop like [`Maze::main_loop`], but reports where the loop breaks instead of panicking.
+    fn trace_main_loop(&self) -> Result<Vec<LoopStep>, MazeIssue> {
+        let iter = self.main_loop();
+        let mut steps = Vec::new();
+        let mut next = iter.next;
+        while let Some(current) = next {
+            steps.push(current);
+            next = iter.advance(current)?;
+        }
+        Ok(steps)
     }
 
-    fn enclosed_tiles(&self, main_loop: HashSet<(usize, usize)>) -> HashSet<(usize, usize)> {
+    fn main_loop(&self) -> LoopIter<'_> {
         let (start_row, start_col) = self.start_position;
         let start_tile = &self.tiles[start_row][start_col];
-        let mut traverse_queue = Vec::new();
-
-        match start_tile {
-            Tile::Vertical => traverse_queue.push(((start_row - 1, start_col), SOUTH, 0)),
-            Tile::Horizontal => traverse_queue.push(((start_row, start_col - 1), EAST, 0)),
-            Tile::NorthEast => traverse_queue.push(((start_row - 1, start_col), SOUTH, 0)),
-            Tile::NorthWest => traverse_queue.push(((start_row - 1, start_col), SOUTH, 0)),
-            Tile::SouthEast => traverse_queue.push(((start_row + 1, start_col), NORTH, 0)),
-            Tile::SouthWest => traverse_queue.push(((start_row + 1, start_col), NORTH, 0)),
-            _ => panic!("Invalid start tile {start_tile}")
-        };
-
-        let mut enclosed = HashSet::new();
-        while let Some((next_position, from, distance_from_start)) = traverse_queue.pop() {
-            self.traverse(next_position, from, distance_from_start, &mut traverse_queue, |pos, _, from| {
-                if pos == self.start_position {
-                    return false;
-                }
-                if (from == SOUTH && [Tile::Vertical, Tile::SouthWest].contains(&self.tiles[pos.0][pos.1]))
-                    || (from == WEST && &self.tiles[pos.0][pos.1] == &Tile::NorthWest) {
-                    let mut col = pos.1 + 1;
-                    loop {
-                        if main_loop.contains(&(pos.0, col)) {
-                            return true;
-                        }
-                        if col == self.width {
-                            enclosed.clear();
-                            return false;
-                        }
-
-                        enclosed.insert((pos.0, col));
-                        col += 1;
-                    }
+        // leave the start in the first connected direction, so the loop is entered from the other one
+        let exit = [NORTH, SOUTH, WEST, EAST]
+            .into_iter()
+            .find(|direction| start_tile.connections() & direction != 0)
+            .unwrap_or_else(|| panic!("Invalid start tile {start_tile}"));
+        let from = start_tile.connections() & !exit;
+
+        LoopIter { maze: self, next: Some(LoopStep { position: self.start_position, from, step: 0 }) }
+    }
+
+    fn farthest_distance_in_loop(&self) -> u32 {
+        (self.main_loop().count() / 2) as u32
+    }
+
+    fn calculate_main_loop(&self) -> HashSet<(usize, usize)> {
+        self.main_loop().map(|loop_step| loop_step.position).collect()
+    }
+
+    fn enclosure(&self, mode: EnclosureMode) -> Enclosure {
+        match mode {
+            EnclosureMode::Parity => self.classify_tiles(),
+            EnclosureMode::Squeeze => self.flood_fill_from_outside(),
+        }
+    }
+
+    /// Floods a grid of doubled resolution from its border. Tile `(row, col)` lies at
+    /// `(2 * row + 1, 2 * col + 1)`, and the cells in between represent the gaps between adjacent
+    /// tiles, which are only blocked if the main loop connects both tiles. Tiles that cannot be
+    /// reached are enclosed.
+    fn flood_fill_from_outside(&self) -> Enclosure {
+        let main_loop = self.calculate_main_loop();
+        let (fine_height, fine_width) = (2 * self.height + 1, 2 * self.width + 1);
+        let mut blocked = vec![vec![false; fine_width]; fine_height];
+        for &(row, col) in &main_loop {
+            let (fine_row, fine_col) = (2 * row + 1, 2 * col + 1);
+            let connections = self.tiles[row][col].connections();
+            blocked[fine_row][fine_col] = true;
+            for direction in [NORTH, SOUTH, EAST, WEST] {
+                if connections & direction != 0 {
+                    let (gap_row, gap_col) = step_towards((fine_row, fine_col), direction);
+                    blocked[gap_row][gap_col] = true;
                 }
-                true
-            });
+            }
         }
 
-        if enclosed.len() != 0 {
-            return enclosed;
+        let mut reachable = vec![vec![false; fine_width]; fine_height];
+        let mut queue = VecDeque::from([(0usize, 0usize)]);
+        reachable[0][0] = true;
+        while let Some((row, col)) = queue.pop_front() {
+            let neighbours = [
+                (row.wrapping_sub(1), col),
+                (row + 1, col),
+                (row, col.wrapping_sub(1)),
+                (row, col + 1),
+            ];
+            for (next_row, next_col) in neighbours {
+                if next_row < fine_height && next_col < fine_width
+                    && !blocked[next_row][next_col] && !reachable[next_row][next_col] {
+                    reachable[next_row][next_col] = true;
+                    queue.push_back((next_row, next_col));
+                }
+            }
         }
 
-        let mut traverse_queue = Vec::new();
-        match start_tile {
-            Tile::Vertical => traverse_queue.push(((start_row - 1, start_col), SOUTH, 0)),
-            Tile::Horizontal => traverse_queue.push(((start_row, start_col - 1), EAST, 0)),
-            Tile::NorthEast => traverse_queue.push(((start_row - 1, start_col), SOUTH, 0)),
-            Tile::NorthWest => traverse_queue.push(((start_row - 1, start_col), SOUTH, 0)),
-            Tile::SouthEast => traverse_queue.push(((start_row + 1, start_col), NORTH, 0)),
-            Tile::SouthWest => traverse_queue.push(((start_row + 1, start_col), NORTH, 0)),
-            _ => panic!("Invalid start tile {start_tile}")
-        };
-
-        while let Some((next_position, from, distance_from_start)) = traverse_queue.pop() {
-            self.traverse(next_position, from, distance_from_start, &mut traverse_queue, |pos, _, from| {
-                if pos == self.start_position {
-                    return false;
+        let mut enclosure = Enclosure { inside: HashSet::new(), outside: HashSet::new() };
+        for row in 0..self.height {
+            for col in (0..self.width).filter(|&col| !main_loop.contains(&(row, col))) {
+                if reachable[2 * row + 1][2 * col + 1] {
+                    enclosure.outside.insert((row, col));
+                } else {
+                    enclosure.inside.insert((row, col));
                 }
-                if (from == SOUTH && [Tile::Vertical, Tile::SouthEast].contains(&self.tiles[pos.0][pos.1]))
-                    || (from == EAST && &self.tiles[pos.0][pos.1] == &Tile::NorthEast) {
-                    let mut col = pos.1 - 1;
-                    loop {
-                        if main_loop.contains(&(pos.0, col)) {
-                            return true;
-                        }
-                        if col == 0 {
-                            enclosed.clear();
-                            return false;
-                        }
-
-                        enclosed.insert((pos.0, col));
-                        col -= 1;
+            }
+        }
+        enclosure
+    }
+
+    /// Classifies every tile that is not part of the main loop as inside or outside of it. Each row
+    /// is swept from left to right, toggling between outside and inside whenever the sweep crosses
+    /// a loop tile that connects to the north. Pipes that do not belong to the main loop are
+    /// treated like ground.
+    fn classify_tiles(&self) -> Enclosure {
+        let main_loop = self.calculate_main_loop();
+        let mut enclosure = Enclosure { inside: HashSet::new(), outside: HashSet::new() };
+        for (row, tiles) in self.tiles.iter().enumerate() {
+            let mut sweep_state = SweepState::Closed;
+            for (col, tile) in tiles.iter().enumerate() {
+                if main_loop.contains(&(row, col)) {
+                    if tile.connections() & NORTH != 0 {
+                        sweep_state.toggle();
                     }
+                } else if sweep_state.is_open() {
+                    enclosure.inside.insert((row, col));
+                } else {
+                    enclosure.outside.insert((row, col));
                 }
-                true
-            });
+            }
         }
-
-        enclosed
+        enclosure
     }
 }
 
 impl Tile {
-    fn connects_horizontally_to_previous(&self, other: &Tile) -> bool {
+    fn from_connections(connections: usize) -> Option<Tile> {
+        match connections {
+            c if c == (NORTH | SOUTH) => Some(Tile::Vertical),
+            c if c == (EAST | WEST) => Some(Tile::Horizontal),
+            c if c == (NORTH | EAST) => Some(Tile::NorthEast),
+            c if c == (NORTH | WEST) => Some(Tile::NorthWest),
+            c if c == (SOUTH | EAST) => Some(Tile::SouthEast),
+            c if c == (SOUTH | WEST) => Some(Tile::SouthWest),
+            _ => None
+        }
+    }
+
+    fn box_glyph(&self) -> char {
         match self {
-            Tile::Horizontal | Tile::NorthWest | Tile::SouthWest if [Tile::NorthEast, Tile::SouthEast, Tile::Horizontal].contains(&other) => true,
-            _ => false
+            Tile::Air => '·',
+            Tile::Start => 'S',
+            Tile::Vertical => '│',
+            Tile::Horizontal => '─',
+            Tile::NorthEast => '└',
+            Tile::NorthWest => '┘',
+            Tile::SouthEast => '┌',
+            Tile::SouthWest => '┐',
         }
     }
 
-    fn connects_vertically_to_previous(&self, other: &Tile) -> bool {
+    fn connections(&self) -> usize {
         match self {
-            Tile::Vertical | Tile::NorthEast | Tile::NorthWest if [Tile::Vertical, Tile::SouthWest, Tile::SouthEast].contains(&other) => true,
-            _ => false
+            Tile::Vertical => NORTH | SOUTH,
+            Tile::Horizontal => EAST | WEST,
+            Tile::NorthEast => NORTH | EAST,
+            Tile::NorthWest => NORTH | WEST,
+            Tile::SouthEast => SOUTH | EAST,
+            Tile::SouthWest => SOUTH | WEST,
+            Tile::Air | Tile::Start => 0,
         }
     }
 }
@@ -369,8 +560,8 @@ enum SweepState {
 }
 
 impl SweepState {
-    fn toggle(&mut self) -> SweepState {
-        match self {
+    fn toggle(&mut self) {
+        *self = match self {
             SweepState::Open => SweepState::Closed,
             SweepState::Closed => SweepState::Open
         }
@@ -386,7 +577,8 @@ impl SweepState {
 
 #[cfg(test)]
 mod tests {
-    use crate::day10::pipe_maze::{enclosed_tiles, parse_maze, Tile};
+    use std::collections::HashSet;
+    use crate::day10::pipe_maze::{EAST, enclosed_tiles, EnclosureMode, find_maze_issues, LoopStep, MazeIssue, NORTH, parse_maze, render_maze, SOUTH, squeezed_enclosed_tiles, Tile, validate_maze, WEST};
 
     #[test]
     fn should_parse_maze() {
@@ -415,6 +607,23 @@ mod tests {
         assert_eq!(maze.farthest_distance_in_loop(), 4);
     }
 
+    #[test]
+    fn should_iterate_main_loop() {
+        let input = r#".....
+.S-7.
+.|.|.
+.L-J.
+....."#;
+        let maze = parse_maze(input);
+        let main_loop = maze.main_loop().collect::<Vec<_>>();
+
+        assert_eq!(main_loop.len(), 8);
+        assert_eq!(main_loop[0], LoopStep { position: (1, 1), from: EAST, step: 0 });
+        assert_eq!(main_loop[1], LoopStep { position: (2, 1), from: NORTH, step: 1 });
+        assert_eq!(main_loop[5], LoopStep { position: (2, 3), from: SOUTH, step: 5 });
+        assert_eq!(main_loop[7], LoopStep { position: (1, 2), from: EAST, step: 7 });
+    }
+
     #[test]
     fn should_traverse_complex_maze() {
         let input = r#"..F7.
@@ -482,5 +691,118 @@ L7JLJL-JLJLJL--JLJ.L"#;
 
         assert_eq!(enclosed_tiles(input), 10);
     }
-}
 
+    #[test]
+    fn should_classify_tiles_with_junk_pipes() {
+        let input = r#"-L|F7
+7S-7|
+L|7||
+-L-J|
+L|-JF"#;
+        let maze = parse_maze(input);
+        let enclosure = maze.classify_tiles();
+
+        assert_eq!(enclosure.inside, HashSet::from([(2, 2)]));
+        assert_eq!(enclosure.outside.len(), 25 - 8 - 1);
+        assert!(enclosure.outside.contains(&(0, 0)));
+        assert!(enclosure.outside.contains(&(4, 4)));
+    }
+
+    #[test]
+    fn should_count_squeezed_enclosed_tiles() {
+        let input = r#"..........
+.S------7.
+.|F----7|.
+.||....||.
+.||....||.
+.|L-7F-J|.
+.|..||..|.
+.L--JL--J.
+.........."#;
+        assert_eq!(squeezed_enclosed_tiles(input), 4);
+
+        let maze = parse_maze(input);
+        let squeezed = maze.enclosure(EnclosureMode::Squeeze);
+        assert!(squeezed.outside.contains(&(3, 3)));
+        assert!(squeezed.inside.contains(&(6, 2)));
+    }
+
+    #[test]
+    fn should_match_parity_and_squeeze_modes() {
+        let input = r#"FF7FSF7F7F7F7F7F---7
+L|LJ||||||||||||F--J
+FL-7LJLJ||||||LJL-77
+F--JF--7||LJLJ7F7FJ-
+L---JF-JLJ.||-FJLJJ7
+|F|F-JF---7F7-L7L|7|
+|FFJF7L7F-JF7|JL---7
+7-L-JL7||F7|L7F-7F7|
+L.L7LFJ|||||FJL7||LJ
+L7JLJL-JLJLJL--JLJ.L"#;
+        let maze = parse_maze(input);
+        let parity = maze.enclosure(EnclosureMode::Parity);
+        let squeezed = maze.enclosure(EnclosureMode::Squeeze);
+
+        assert_eq!(squeezed.inside.len(), 10);
+        assert_eq!(parity.inside, squeezed.inside);
+        assert_eq!(parity.outside, squeezed.outside);
+    }
+
+    #[test]
+    fn should_render_maze() {
+        let input = r#"-L|F7
+7S-7|
+L|7||
+-L-J|
+L|-JF"#;
+        let expected = r#"─└│┌┐
+┐S─┐│
+└│I││
+─└─*│
+└│─┘┌
+"#;
+        assert_eq!(render_maze(input, false), expected);
+
+        let colored = render_maze(input, true);
+        assert!(colored.contains("\x1b[1;42;30mS\x1b[0m"));
+        assert!(colored.contains("\x1b[32mI\x1b[0m"));
+    }
+
+    #[test]
+    fn should_validate_maze() {
+        let input = r#".....
+.S-7.
+.|.|.
+.L-J.
+....."#;
+        assert!(find_maze_issues(input).is_empty());
+
+        let input = r#".....
+.S-7.
+.|.|.
+.L-..
+....."#;
+        assert_eq!(find_maze_issues(input), vec![
+            MazeIssue::DanglingEnd { position: (2, 3), direction: SOUTH },
+            MazeIssue::DanglingEnd { position: (3, 2), direction: EAST },
+            MazeIssue::LoopBreak { position: (3, 3), from: WEST },
+        ]);
+    }
+
+    #[test]
+    fn should_suggest_start_tiles_for_ambiguous_start() {
+        let input = r#".....
+.F-7.
+.|.|.
+-S-J.
+.|..."#;
+        let issues = find_maze_issues(input);
+        assert_eq!(issues.last(), Some(&MazeIssue::AmbiguousStart { position: (3, 1), closing_tiles: vec![Tile::NorthEast] }));
+        assert!(validate_maze(input).contains(&"(3, 1) start connects to more than 2 neighbors, tiles closing the loop: [L]".to_string()));
+
+        let input = r#"S-7
+|.|
+L-."#;
+        assert_eq!(find_maze_issues(input).last(), Some(&MazeIssue::LoopBreak { position: (2, 2), from: WEST }));
+    }
+}
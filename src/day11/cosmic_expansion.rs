@@ -1,48 +1,83 @@
-use std::collections::HashSet;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
-struct Universe {
+/// An image of the universe. Empty rows and columns are `expansion_factor` times as wide as they
+/// appear in the image. The galaxies are numbered and placed whenever the factor is set, so that
+/// queries never rescan the grid.
+pub(crate) struct Universe {
     grid: Vec<Vec<Content>>,
+    expansion_factor: u64,
+    galaxies: Vec<Galaxy>,
 }
 
-#[derive(Clone, PartialEq)]
-enum Content {
-    Space,
-    Galaxy,
-    ExpandedSpace,
+/// A galaxy numbered from 1 in reading order, like in the puzzle description, at its expanded
+/// position.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) struct Galaxy {
+    pub(crate) number: usize,
+    pub(crate) row: u64,
+    pub(crate) column: u64,
 }
 
-pub fn all_pairs_shortest_path(input: &str) -> usize {
-    let universe = Universe::from_str(input).unwrap();
-    let expanded_universe = universe.expand();
-    let galaxy_positions = expanded_universe.galaxy_positions();
-    Universe::galaxy_pairs(galaxy_positions)
-        .into_iter()
-        .map(|(source, target)| manhattan_distance(source, target))
-        .sum()
+/// The closest and the most distant other galaxy, as galaxy number and distance. Ties go to the
+/// galaxy with the lower number.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct Neighbours {
+    pub(crate) galaxy: usize,
+    pub(crate) nearest: (usize, u64),
+    pub(crate) farthest: (usize, u64),
 }
 
-pub fn all_pairs_million_times_expand_shortest_path(input: &str) -> usize {
-    let universe = Universe::from_str(input).unwrap();
-    let huge_universe = universe.huge_expand();
-    let galaxy_positions = huge_universe.galaxy_positions();
-    let (huge_rows, huge_columns) = huge_universe.huge_grid_elements();
-    Universe::galaxy_pairs(galaxy_positions)
-        .into_iter()
-        .map(|(source, target)| modified_manhattan_distance(source, target, &huge_rows, &huge_columns))
-        .sum()
+#[derive(Debug, PartialEq)]
+pub enum ExpansionError {
+    TooLarge { factor: u64 },
+    SumTooLarge,
 }
 
-impl Content {
-    fn distance(&self) -> usize {
+impl Display for ExpansionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Content::Space | Content::Galaxy => 1,
-            Content::ExpandedSpace => 1_000_000,
+            ExpansionError::TooLarge { factor } => write!(f, "Universe expanded by {} does not fit into a u64", factor),
+            ExpansionError::SumTooLarge => write!(f, "Sum of all galaxy distances does not fit into a u64"),
         }
     }
 }
 
+#[derive(Clone, PartialEq)]
+enum Content {
+    Space,
+    Galaxy,
+}
+
+/// Sums the distances between all pairs of galaxies, after every empty row and column has been
+/// replaced by `factor` empty rows or columns.
+pub fn all_pairs_shortest_path(input: &str, factor: u64) -> Result<u64, ExpansionError> {
+    let universe = Universe::from_str(input).unwrap().with_expansion_factor(factor)?;
+    let (rows, columns) = universe.galaxies().iter().map(|galaxy| (galaxy.row, galaxy.column)).unzip();
+    sum_of_pairwise_differences(rows)
+        .zip(sum_of_pairwise_differences(columns))
+        .and_then(|(rows, columns)| rows.checked_add(columns))
+        .and_then(|sum| u64::try_from(sum).ok())
+        .ok_or(ExpansionError::SumTooLarge)
+}
+
+/// Sums `|a - b|` over all pairs of coordinates on one axis. After sorting, every coordinate is
+/// larger than all coordinates before it, so it contributes `index * coordinate` minus the sum of
+/// its predecessors. Accumulates in `u128` and returns `None` if even that overflows.
+fn sum_of_pairwise_differences(mut coordinates: Vec<u64>) -> Option<u128> {
+    coordinates.sort_unstable();
+    let mut prefix_sum = 0u128;
+    let mut total = 0u128;
+    for (index, coordinate) in coordinates.into_iter().enumerate() {
+        let contribution = (index as u128).checked_mul(coordinate as u128)? - prefix_sum;
+        total = total.checked_add(contribution)?;
+        prefix_sum = prefix_sum.checked_add(coordinate as u128)?;
+    }
+    Some(total)
+}
+
 impl FromStr for Universe {
     type Err = ();
 
@@ -54,7 +89,9 @@ impl FromStr for Universe {
                 _ => panic!("Unknown content '{}'", c)
             }).collect::<Vec<_>>()
         }).collect::<Vec<_>>();
-        Ok(Universe { grid })
+        Universe { grid, expansion_factor: 1, galaxies: Vec::new() }
+            .with_expansion_factor(2)
+            .map_err(|_| ())
     }
 }
 
@@ -64,7 +101,6 @@ impl Display for Universe {
             for content in row {
                 match content {
                     Content::Space=> write!(f, ".")?,
-                    Content::ExpandedSpace => write!(f, "o")?,
                     Content::Galaxy => write!(f, "#")?,
                 }
             }
@@ -75,74 +111,50 @@ impl Display for Universe {
 }
 
 impl Universe {
-    fn expand(&self) -> Self {
-        let mut grid = self.grid.clone();
-
-        // scan for empty rows
-        let mut row_offset = 0;
-        let row_length = self.grid[0].len();
-        for (row, grid_row) in self.grid.iter().enumerate() {
-            if grid_row.iter().all(|c| c == &Content::Space) {
-                grid.insert(row + row_offset, vec![Content::Space; row_length]);
-                row_offset += 1;
-            }
-        }
-
-        // scan for empty columns
-        let mut column_offset = 0;
-        let column_length = grid.len();
-        for column in 0..self.grid[0].len() {
-            let mut all_empty = true;
-            for row in 0..column_length {
-                if grid[row][column + column_offset] != Content::Space {
-                    all_empty = false;
-                    break;
-                }
-            }
-
-            if all_empty {
-                for row in 0..column_length {
-                    grid[row].insert(column + column_offset, Content::Space);
-                }
-                column_offset += 1;
-            }
-        }
-
-        Universe { grid }
+    /// Fails if the expanded height plus the expanded width does not fit into a `u64`, which
+    /// guarantees that every coordinate and every distance between two galaxies fits.
+    pub(crate) fn with_expansion_factor(self, expansion_factor: u64) -> Result<Self, ExpansionError> {
+        let universe = Universe { expansion_factor, ..self };
+        let galaxies = universe.expanded_galaxies().ok_or(ExpansionError::TooLarge { factor: expansion_factor })?;
+        Ok(Universe { galaxies, ..universe })
     }
 
-    fn huge_expand(&self) -> Self {
-        let mut grid = self.grid.clone();
+    /// All galaxies at their position after expansion.
+    pub(crate) fn galaxies(&self) -> &[Galaxy] {
+        &self.galaxies
+    }
 
-        // scan for empty rows
-        for (row, grid_row) in self.grid.iter().enumerate() {
-            if grid_row.iter().all(|c| c == &Content::Space) {
-                grid[row] = vec![Content::ExpandedSpace; self.grid[0].len()];
-            }
-        }
+    /// Distance between two galaxies given by their number.
+    pub(crate) fn distance(&self, source: usize, target: usize) -> Option<u64> {
+        let galaxy = |number: usize| number.checked_sub(1).and_then(|index| self.galaxies.get(index));
+        Some(manhattan_distance(galaxy(source)?, galaxy(target)?))
+    }
 
-        // scan for empty columns
-        let column_length = grid.len();
-        for column in 0..self.grid[0].len() {
-            let mut all_empty = true;
-            for row in 0..column_length {
-                match grid[row][column] {
-                    Content::Galaxy => {
-                        all_empty = false;
-                        break;
-                    }
-                    Content::ExpandedSpace | Content::Space => continue
-                }
-            }
+    /// Nearest and farthest neighbour of every galaxy. Universes with fewer than two galaxies have
+    /// no neighbours at all. Compares every pair of galaxies, so this is quadratic in the number of
+    /// galaxies by design; sums over all pairs are cheaper with [`all_pairs_shortest_path`].
+    pub(crate) fn neighbours(&self) -> Vec<Neighbours> {
+        self.galaxies.iter().filter_map(|galaxy| {
+            let distances = self.galaxies
+                .iter()
+                .filter(|other| other.number != galaxy.number)
+                .map(|other| (other.number, manhattan_distance(galaxy, other)));
+            let nearest = distances.clone().min_by_key(|&(number, distance)| (distance, number))?;
+            let farthest = distances.max_by_key(|&(number, distance)| (distance, Reverse(number)))?;
+            Some(Neighbours { galaxy: galaxy.number, nearest, farthest })
+        }).collect()
+    }
 
-            if all_empty {
-                for row in 0..column_length {
-                    grid[row][column] = Content::ExpandedSpace;
-                }
+    /// Number of galaxy pairs per distance. Like [`Universe::neighbours`], this visits every pair
+    /// and is quadratic in the number of galaxies.
+    pub(crate) fn distance_histogram(&self) -> BTreeMap<u64, usize> {
+        let mut histogram = BTreeMap::new();
+        for (index, source) in self.galaxies.iter().enumerate() {
+            for target in &self.galaxies[index + 1..] {
+                *histogram.entry(manhattan_distance(source, target)).or_insert(0) += 1;
             }
         }
-
-        Universe { grid }
+        histogram
     }
 
     fn galaxy_positions(&self) -> Vec<(usize, usize)> {
@@ -157,67 +169,63 @@ impl Universe {
         positions
     }
 
-    fn huge_grid_elements(&self) -> (HashSet<usize>, HashSet<usize>) {
-        let mut huge_rows = HashSet::new();
-        for row in 0..self.grid.len() {
-            if self.grid[row][0] == Content::ExpandedSpace {
-                huge_rows.insert(row);
-            }
-        }
-
-        let mut huge_columns = HashSet::new();
-        for column in 0..self.grid[0].len() {
-            if self.grid[0][column] == Content::ExpandedSpace {
-                huge_columns.insert(column);
-            }
-        }
-
-        (huge_rows, huge_columns)
+    /// Numbers and places all galaxies. Instead of inserting rows and columns, every coordinate is
+    /// shifted by the number of empty rows or columns before it. Returns `None` if the expanded
+    /// height plus the expanded width does not fit into a `u64`.
+    fn expanded_galaxies(&self) -> Option<Vec<Galaxy>> {
+        let (empty_rows, empty_columns) = self.empty_rows_and_columns();
+        let row_offsets = self.expanded_offsets(&empty_rows)?;
+        let column_offsets = self.expanded_offsets(&empty_columns)?;
+        row_offsets.last()?.checked_add(*column_offsets.last()?)?;
+
+        let galaxies = self.galaxy_positions()
+            .into_iter()
+            .enumerate()
+            .map(|(index, (row, column))| Galaxy {
+                number: index + 1,
+                row: row_offsets[row],
+                column: column_offsets[column],
+            })
+            .collect();
+        Some(galaxies)
     }
 
-    fn galaxy_pairs(positions: Vec<(usize, usize)>) -> Vec<((usize, usize), (usize, usize))> {
-        let mut pairs = Vec::new();
-        for source in 0..positions.len() {
-            for target in source + 1..positions.len() {
-                pairs.push((positions[source], positions[target]))
-            }
+    /// The expanded offset of every row or column, followed by the expanded length of the axis.
+    /// Returns `None` if the length does not fit into a `u64`.
+    fn expanded_offsets(&self, empty: &[bool]) -> Option<Vec<u64>> {
+        let mut offsets = vec![0u64];
+        for &is_empty in empty {
+            let width = if is_empty { self.expansion_factor } else { 1 };
+            offsets.push(offsets.last()?.checked_add(width)?);
         }
-        pairs
+        Some(offsets)
     }
-}
-
-fn manhattan_distance(a: (usize, usize), b: (usize, usize)) -> usize {
-    let (a_row, a_column) = a;
-    let (b_row, b_column) = b;
-    ((a_row as i32 - b_row as i32).abs() + (a_column as i32 - b_column as i32).abs()) as usize
-}
 
-fn modified_manhattan_distance(a: (usize, usize), b: (usize, usize), huge_rows: &HashSet<usize>, huge_columns: &HashSet<usize>) -> usize {
-    let (a_row, a_column) = a;
-    let (b_row, b_column) = b;
-    let mut distance = ((a_row as i32 - b_row as i32).abs() + (a_column as i32 - b_column as i32).abs()) as usize;
-    for row in a_row.min(b_row)..a_row.max(b_row) {
-        if huge_rows.contains(&row) {
-           distance += Content::ExpandedSpace.distance() - 1;
-        }
-    }
-    for column in a_column.min(b_column)..a_column.max(b_column) {
-        if huge_columns.contains(&column) {
-           distance += Content::ExpandedSpace.distance() - 1;
-        }
+    fn empty_rows_and_columns(&self) -> (Vec<bool>, Vec<bool>) {
+        let empty_rows = self.grid
+            .iter()
+            .map(|grid_row| grid_row.iter().all(|c| c == &Content::Space))
+            .collect();
+        let empty_columns = (0..self.grid[0].len())
+            .map(|column| self.grid.iter().all(|grid_row| grid_row[column] == Content::Space))
+            .collect();
+        (empty_rows, empty_columns)
     }
+}
 
-    distance
+/// Cannot overflow, as [`Universe::with_expansion_factor`] ensures that height plus width fits.
+fn manhattan_distance(a: &Galaxy, b: &Galaxy) -> u64 {
+    a.row.abs_diff(b.row) + a.column.abs_diff(b.column)
 }
 
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
 
-    use crate::day11::cosmic_expansion::Universe;
+    use crate::day11::cosmic_expansion::{ExpansionError, Galaxy, Neighbours, sum_of_pairwise_differences, Universe};
 
     #[test]
-    fn should_expand_image() {
+    fn should_sum_galaxy_distances() {
         let input = r#"...#......
 .......#..
 #.........
@@ -228,25 +236,11 @@ mod tests {
 ..........
 .......#..
 #...#....."#;
-        let universe = Universe::from_str(input).unwrap();
-        let expanded_universe = r#"....#........
-.........#...
-#............
-.............
-.............
-........#....
-.#...........
-............#
-.............
-.............
-.........#...
-#....#.......
-"#;
-        assert_eq!(universe.expand().to_string(), expanded_universe);
+        assert_eq!(super::all_pairs_shortest_path(input, 2), Ok(374));
     }
 
     #[test]
-    fn should_sum_galaxy_distances() {
+    fn should_find_empty_rows_and_columns() {
         let input = r#"...#......
 .......#..
 #.........
@@ -257,11 +251,15 @@ mod tests {
 ..........
 .......#..
 #...#....."#;
-        assert_eq!(super::all_pairs_shortest_path(input), 374);
+        let universe = Universe::from_str(input).unwrap();
+        let (empty_rows, empty_columns) = universe.empty_rows_and_columns();
+        let indices = |empty: Vec<bool>| empty.iter().enumerate().filter(|(_, e)| **e).map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(indices(empty_rows), vec![3, 7]);
+        assert_eq!(indices(empty_columns), vec![2, 5, 8]);
     }
 
     #[test]
-    fn should_huge_expand_image() {
+    fn should_sum_huge_galaxy_distances() {
         let input = r#"...#......
 .......#..
 #.........
@@ -272,24 +270,28 @@ mod tests {
 ..........
 .......#..
 #...#....."#;
-        let universe = Universe::from_str(input).unwrap();
-        let expanded_universe = r#"..o#.o..o.
-..o..o.#o.
-#.o..o..o.
-oooooooooo
-..o..o#.o.
-.#o..o..o.
-..o..o..o#
-oooooooooo
-..o..o.#o.
-#.o.#o..o.
-"#;
-        assert_eq!(universe.huge_expand().to_string(), expanded_universe);
+        assert_eq!(super::all_pairs_shortest_path(input, 10), Ok(1030));
+        assert_eq!(super::all_pairs_shortest_path(input, 100), Ok(8410));
+        assert_eq!(super::all_pairs_shortest_path(input, u64::MAX), Err(ExpansionError::TooLarge { factor: u64::MAX }));
+        assert_eq!(super::all_pairs_shortest_path("##...###", 1 << 62), Err(ExpansionError::SumTooLarge));
+        assert_eq!(super::all_pairs_shortest_path("#...#", 1 << 62), Ok(3 * (1 << 62) + 1));
     }
 
     #[test]
-    fn should_sum_huge_galaxy_distances() {
-        let input = r#"...#......
+    fn should_sum_pairwise_differences() {
+        let cases = [
+            (vec![], 0),
+            (vec![5], 0),
+            (vec![3, 3], 0),
+            (vec![7, 0, 12, 7, 3], 56),
+            (vec![u64::MAX, 0, u64::MAX], 2 * u64::MAX as u128),
+        ];
+        for (coordinates, sum) in cases {
+            assert_eq!(sum_of_pairwise_differences(coordinates), Some(sum));
+        }
+    }
+
+    const INPUT: &str = r#"...#......
 .......#..
 #.........
 ..........
@@ -299,6 +301,48 @@ oooooooooo
 ..........
 .......#..
 #...#....."#;
-        assert_eq!(super::all_pairs_million_times_expand_shortest_path(input), 8410);
+
+    #[test]
+    fn should_number_galaxies_in_reading_order() {
+        let universe = Universe::from_str(INPUT).unwrap();
+        let galaxies = universe.galaxies();
+        assert_eq!(galaxies.len(), 9);
+        assert_eq!(galaxies[0], Galaxy { number: 1, row: 0, column: 4 });
+        assert_eq!(galaxies[8], Galaxy { number: 9, row: 11, column: 5 });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn should_measure_distance_between_numbered_galaxies() {
+        let universe = Universe::from_str(INPUT).unwrap();
+        assert_eq!(universe.distance(5, 9), Some(9));
+        assert_eq!(universe.distance(1, 7), Some(15));
+        assert_eq!(universe.distance(3, 6), Some(17));
+        assert_eq!(universe.distance(8, 9), Some(5));
+        assert_eq!(universe.distance(0, 9), None);
+        assert_eq!(universe.distance(1, 10), None);
+
+        let universe = universe.with_expansion_factor(10).unwrap();
+        assert_eq!(universe.distance(8, 9), Some(4 + 9));
+        assert_eq!(universe.distance(1, 7), Some(12 + 3 * 9));
+    }
+
+    #[test]
+    fn should_find_nearest_and_farthest_neighbours() {
+        let universe = Universe::from_str(INPUT).unwrap();
+        let neighbours = universe.neighbours();
+        assert_eq!(neighbours.len(), 9);
+        assert_eq!(neighbours[8], Neighbours { galaxy: 9, nearest: (7, 5), farthest: (2, 14) });
+        assert!(Universe::from_str("#.\n..").unwrap().neighbours().is_empty());
+    }
+
+    #[test]
+    fn should_count_distances_in_histogram() {
+        for factor in [2, 10, 100] {
+            let universe = Universe::from_str(INPUT).unwrap().with_expansion_factor(factor).unwrap();
+            let histogram = universe.distance_histogram();
+            assert_eq!(histogram.values().sum::<usize>(), 36);
+            let total = histogram.iter().map(|(distance, count)| distance * *count as u64).sum::<u64>();
+            assert_eq!(Ok(total), super::all_pairs_shortest_path(INPUT, factor));
+        }
+    }
+}
@@ -1,6 +1,6 @@
-use std::collections::HashMap;
 use std::fmt::{Debug, Display, Formatter};
 use std::iter::Peekable;
+use std::ops::Range;
 use std::slice::Iter;
 use std::str::FromStr;
 
@@ -18,19 +18,59 @@ impl From<char> for SpringState {
 impl From<(&str, usize)> for SpringRecord {
 
     fn from((s, copies): (&str, usize)) -> Self {
+        SpringRecord::from((s, &Unfolding::new(copies)))
+    }
+}
+
+impl From<(&str, &Unfolding)> for SpringRecord {
+
+    fn from((s, unfolding): (&str, &Unfolding)) -> Self {
         let (row, damage_group) = s.split_once(' ').unwrap();
-        let mut state_row: Vec<_> = row.chars().map(|c| c.into()).collect();
-        let mut damage_groups: Vec<_> = damage_group.split(',').map(|s| s.parse::<usize>().unwrap()).collect();
+        let state_row: Vec<SpringState> = row.chars().map(|c| c.into()).collect();
+        let damage_groups: Vec<_> = damage_group.split(',').map(|s| s.parse::<usize>().unwrap()).collect();
+
+        let mut unfolded_state_row = Vec::new();
+        for copy in 0..unfolding.pattern_copies {
+            if let Some(separator) = unfolding.separator.as_ref().filter(|_| copy > 0) {
+                unfolded_state_row.push(separator.clone());
+            }
+            unfolded_state_row.extend(state_row.iter().cloned());
+        }
+        let unfolded_damage_groups = damage_groups.repeat(unfolding.group_copies);
+        SpringRecord { state_row: unfolded_state_row, damage_groups: unfolded_damage_groups }
+    }
+}
 
-        let state_row_copy = state_row.clone();
-        let damage_groups_copy = damage_groups.clone();
+/// How a record is unfolded: the pattern is repeated `pattern_copies` times, joined by the
+/// separator if there is one, and the damage groups are repeated `group_copies` times.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct Unfolding {
+    pattern_copies: usize,
+    group_copies: usize,
+    separator: Option<SpringState>,
+}
+
+impl Unfolding {
+    /// The unfolding from the puzzle: pattern and groups are both copied, and the patterns are
+    /// joined by an unknown spring.
+    pub(crate) fn new(copies: usize) -> Self {
+        Unfolding { pattern_copies: copies, group_copies: copies, separator: Some(SpringState::Unknown) }
+    }
 
-        (0..copies - 1).for_each(|_| {
-            state_row.push(SpringState::Unknown);
-            state_row.extend(state_row_copy.clone());
-            damage_groups.extend(damage_groups_copy.clone());
-        });
-        SpringRecord { state_row, damage_groups, copies }
+    pub(crate) fn with_separator(self, separator: SpringState) -> Self {
+        Unfolding { separator: Some(separator), ..self }
+    }
+
+    pub(crate) fn without_separator(self) -> Self {
+        Unfolding { separator: None, ..self }
+    }
+
+    pub(crate) fn with_pattern_copies(self, pattern_copies: usize) -> Self {
+        Unfolding { pattern_copies, ..self }
+    }
+
+    pub(crate) fn with_group_copies(self, group_copies: usize) -> Self {
+        Unfolding { group_copies, ..self }
     }
 }
 
@@ -64,169 +104,183 @@ impl Display for SpringRecord {
 }
 
 pub fn count_arrangements(input: &str, copies: usize) -> usize {
-    let spring_records = parse_input(input, copies);
-    spring_records.iter()
-        .map(|record| record.find_valid_replacements_for_unknown())
-        .sum()
+    count_unfolded_arrangements(input, &Unfolding::new(copies))
 }
 
-fn parse_input(input: &str, copies: usize) -> Vec<SpringRecord> {
-    input.lines().map(|line| SpringRecord::from((line, copies))).collect()
+/// Counts the arrangements of all records unfolded by `unfolding`, splitting the records across the
+/// available threads.
+pub(crate) fn count_unfolded_arrangements(input: &str, unfolding: &Unfolding) -> usize {
+    let spring_records = parse_input(input, unfolding);
+    let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let chunk_size = spring_records.len().div_ceil(threads).max(1);
+    std::thread::scope(|scope| {
+        let workers = spring_records
+            .chunks(chunk_size)
+            .map(|records| scope.spawn(|| records.iter().map(|record| record.find_valid_replacements_for_unknown()).sum::<usize>()))
+            .collect::<Vec<_>>();
+        workers.into_iter().map(|worker| worker.join().unwrap()).sum()
+    })
+}
+
+fn parse_input(input: &str, unfolding: &Unfolding) -> Vec<SpringRecord> {
+    input.lines().map(|line| SpringRecord::from((line, unfolding))).collect()
 }
 
 impl SpringRecord {
     fn find_valid_replacements_for_unknown(&self) -> usize {
-        let damage_tracker = DamageTracker { damage_group_index: 0, damage_groups: &self.damage_groups, damage_counter: 0, next_can_be_damaged: true };
-        let mut cache = HashMap::new();
-        Self::fork_replacement_search(damage_tracker, self.state_row.clone(), 0, &mut cache)
-    }
-
-    fn fork_replacement_search(
-        mut damage_tracker: DamageTracker,
-        mut state_row: Vec<SpringState>,
-        state_row_start: usize,
-        cache: &mut HashMap<TraversalState, usize>
-    ) -> usize {
-
-        for (col, spring_state) in state_row.iter_mut().enumerate().skip(state_row_start) {
-
-            let valid_state = match spring_state {
-                SpringState::Operational => damage_tracker.allow_next_to_be_damaged(),
-                SpringState::Damaged => damage_tracker.record_damage(),
-                SpringState::Unknown => match damage_tracker.resolve_unknown_spring_state() {
-                    SpringState::Operational => {
-                        *spring_state = SpringState::Operational;
-                        damage_tracker.allow_next_to_be_damaged()
-                    },
-                    SpringState::Damaged => {
-                        *spring_state = SpringState::Damaged;
-                        damage_tracker.record_damage()
-                    },
-                    SpringState::Unknown => {
-                        let key = TraversalState::new(damage_tracker.damage_group_index, col, damage_tracker.damage_counter);
-                        if let Some(entry) = cache.get(&key) {
-                            return *entry;
-                        }
-                        // this can be either Operational or Damaged
-                        let mut forked_state_row = state_row.clone();
-                        forked_state_row[col] = SpringState::Damaged;
-                        state_row[col] = SpringState::Operational;
-                        let fork_result = Self::fork_replacement_search(damage_tracker.clone(), forked_state_row, col, cache) +
-                            Self::fork_replacement_search(damage_tracker.clone(), state_row, col, cache);
-
-                        cache.insert(key, fork_result);
-                        return fork_result;
-
-                    }
-                }
-            };
-            if !valid_state {
-                return 0;
-            }
-        }
-
-        if damage_tracker.is_exhausted() {
-            return 1;
-        }
-        0
+        ArrangementTable::new(self).count(0, 0, 0)
     }
-}
 
-#[derive(Hash, Eq, PartialEq, Clone, Debug)]
-struct TraversalState {
-    normalized_damage_tracker_index: usize,
-    normalized_col: usize,
-    damage_counter: usize
-}
-
-impl TraversalState {
-    fn new(damage_tracker_index: usize, col: usize, damage_counter: usize) -> Self {
-        TraversalState { normalized_damage_tracker_index: damage_tracker_index, normalized_col: col, damage_counter }
+    /// Lazily yields every valid arrangement, with all unknown springs resolved. Arrangements come in
+    /// lexicographic order with operational springs before damaged ones.
+    pub(crate) fn arrangements(&self) -> Arrangements<'_> {
+        let table = ArrangementTable::new(self);
+        Arrangements { record: self, remaining: 0..table.count(0, 0, 0), table }
     }
-}
 
-#[derive(Clone, Debug)]
-struct DamageTracker<'a> {
-    damage_group_index: usize,
-    damage_groups: &'a [usize],
-    damage_counter: usize,
-    next_can_be_damaged: bool
-}
-
-impl<'a> DamageTracker<'a> {
-    fn current_damage(&mut self) -> usize {
-        match self.peek() {
-            Some(damage_group) => damage_group,
-            None => panic!("No more damage groups")
-        }
+    /// Returns the arrangement at `index` in the order of [`SpringRecord::arrangements`], without
+    /// enumerating the ones before it.
+    pub(crate) fn nth_arrangement(&self, index: usize) -> Option<Vec<SpringState>> {
+        ArrangementTable::new(self).nth_arrangement(self, index)
     }
 
-    fn allow_next_to_be_damaged(&mut self) -> bool {
-        if self.damage_group_done() {
-            self.next_can_be_damaged = true;
-            return true
+    /// Picks a valid arrangement uniformly, given a source of uniformly distributed numbers, or
+    /// returns `None` if the record has no valid arrangement. Numbers from the incomplete last
+    /// stretch of `count` values below `u64::MAX` are drawn again, so that taking the remainder
+    /// does not favour the first arrangements.
+    pub(crate) fn sample_arrangement(&self, mut random: impl FnMut() -> u64) -> Option<Vec<SpringState>> {
+        let table = ArrangementTable::new(self);
+        let count = table.count(0, 0, 0) as u64;
+        if count == 0 {
+            return None;
+        }
+        let limit = u64::MAX - u64::MAX % count;
+        let mut number = random();
+        while number >= limit {
+            number = random();
         }
-        false
+        table.nth_arrangement(self, (number % count) as usize)
     }
 
-    fn record_damage(&mut self) -> bool {
-        if self.peek().is_none() || !self.next_can_be_damaged {
-            return false;
+    /// Follows a spring in `state` at `position`, coming from `group` completed damage groups and a
+    /// run of `run` damaged springs. Returns the group index and run length after the spring, or
+    /// `None` if the spring contradicts the row or the damage groups.
+    fn next_state(&self, position: usize, group: usize, run: usize, state: &SpringState) -> Option<(usize, usize)> {
+        if self.state_row[position] != SpringState::Unknown && self.state_row[position] != *state {
+            return None;
         }
-        self.damage_counter += 1;
-        if self.damage_counter == self.current_damage() {
-            self.next_can_be_damaged = false;
-            self.next();
-            self.damage_counter = 0;
+        match state {
+            SpringState::Operational if run == 0 => Some((group, 0)),
+            SpringState::Operational if self.damage_groups.get(group) == Some(&run) => Some((group + 1, 0)),
+            SpringState::Damaged if self.damage_groups.get(group).is_some_and(|&size| run < size) => Some((group, run + 1)),
+            _ => None
         }
-        true
     }
 
-    fn next_can_be_damaged(&self) -> bool {
-        self.next_can_be_damaged
+    /// Whether the row may end after `group` completed damage groups and a run of `run` damaged
+    /// springs.
+    fn is_complete(&self, group: usize, run: usize) -> bool {
+        let groups = self.damage_groups.len();
+        (group == groups && run == 0) || (group + 1 == groups && run == self.damage_groups[group])
     }
+}
 
-    fn damage_group_done(&self) -> bool {
-        self.damage_counter == 0
-    }
+/// Number of valid completions for every state of a row, filled in from the end of the row. A
+/// state is the position in the row, the number of completed damage groups and the length of the
+/// current run of damaged springs.
+struct ArrangementTable {
+    groups: usize,
+    max_run: usize,
+    counts: Vec<usize>,
+}
 
-    fn resolve_unknown_spring_state(&mut self) -> SpringState {
-        if !self.next_can_be_damaged() {
-            SpringState::Operational
-        } else if self.damage_counter > 0 {
-            SpringState::Damaged
-        } else {
-            SpringState::Unknown
+impl ArrangementTable {
+    fn new(record: &SpringRecord) -> Self {
+        let groups = record.damage_groups.len();
+        let max_run = record.damage_groups.iter().copied().max().unwrap_or(0);
+        let mut table = ArrangementTable { groups, max_run, counts: vec![0; (record.state_row.len() + 1) * (groups + 1) * (max_run + 1)] };
+
+        for group in 0..=groups {
+            for run in 0..=max_run {
+                if record.is_complete(group, run) {
+                    let index = table.index(record.state_row.len(), group, run);
+                    table.counts[index] = 1;
+                }
+            }
+        }
+        for position in (0..record.state_row.len()).rev() {
+            for group in 0..=groups {
+                for run in 0..=max_run {
+                    let count = [SpringState::Operational, SpringState::Damaged]
+                        .iter()
+                        .filter_map(|state| record.next_state(position, group, run, state))
+                        .map(|(next_group, next_run)| table.count(position + 1, next_group, next_run))
+                        .sum();
+                    let index = table.index(position, group, run);
+                    table.counts[index] = count;
+                }
+            }
         }
+        table
     }
 
-    fn is_exhausted(&self) -> bool {
-        self.peek().is_none() && self.damage_counter == 0
+    fn index(&self, position: usize, group: usize, run: usize) -> usize {
+        (position * (self.groups + 1) + group) * (self.max_run + 1) + run
     }
 
-    fn peek(&self) -> Option<usize> {
-        if self.damage_group_index >= self.damage_groups.len() {
+    fn count(&self, position: usize, group: usize, run: usize) -> usize {
+        self.counts[self.index(position, group, run)]
+    }
+
+    /// Walks the row from the start and takes the branch holding the arrangement at `index` at every
+    /// unknown spring, as told by the counts of both branches.
+    fn nth_arrangement(&self, record: &SpringRecord, mut index: usize) -> Option<Vec<SpringState>> {
+        if index >= self.count(0, 0, 0) {
             return None;
         }
-        Some(self.damage_groups[self.damage_group_index])
+        let (mut group, mut run) = (0, 0);
+        let mut state_row = Vec::with_capacity(record.state_row.len());
+        for position in 0..record.state_row.len() {
+            for state in [SpringState::Operational, SpringState::Damaged] {
+                if let Some((next_group, next_run)) = record.next_state(position, group, run, &state) {
+                    let count = self.count(position + 1, next_group, next_run);
+                    if index < count {
+                        (group, run) = (next_group, next_run);
+                        state_row.push(state);
+                        break;
+                    }
+                    index -= count;
+                }
+            }
+        }
+        Some(state_row)
     }
+}
 
-    fn next(&mut self) {
-        self.damage_group_index += 1
-    }
+/// Valid arrangements of a record, each resolved from its index in the arrangement table.
+pub(crate) struct Arrangements<'a> {
+    record: &'a SpringRecord,
+    table: ArrangementTable,
+    remaining: Range<usize>,
 }
 
+impl<'a> Iterator for Arrangements<'a> {
+    type Item = Vec<SpringState>;
 
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.remaining.next()?;
+        self.table.nth_arrangement(self.record, index)
+    }
+}
 
 #[derive(Debug)]
-struct SpringRecord {
+pub(crate) struct SpringRecord {
     state_row: Vec<SpringState>,
     damage_groups: Vec<usize>,
-    copies: usize
 }
 
-#[derive(Clone, PartialEq)]
-enum SpringState {
+#[derive(Clone, Eq, PartialEq)]
+pub(crate) enum SpringState {
     Operational,
     Damaged,
     Unknown
@@ -257,4 +311,112 @@ mod tests {
 ?###???????? 3,2,1"#;
         assert_eq!(count_arrangements(input, 5), 525152);
     }
-}
\ No newline at end of file
+
+    fn to_string(state_row: &[SpringState]) -> String {
+        state_row.iter().map(|state| state.to_string()).collect()
+    }
+
+    /// Counts by trying every combination of unknown springs, only feasible for short records.
+    fn brute_force_count(record: &SpringRecord) -> usize {
+        let unknowns = record.state_row.iter().enumerate().filter(|(_, state)| **state == SpringState::Unknown).map(|(col, _)| col).collect::<Vec<_>>();
+        (0..1usize << unknowns.len()).filter(|combination| {
+            let mut state_row = record.state_row.clone();
+            for (bit, col) in unknowns.iter().enumerate() {
+                state_row[*col] = if combination & (1 << bit) == 0 { SpringState::Operational } else { SpringState::Damaged };
+            }
+            let damage_groups = to_string(&state_row).split('.').filter(|run| !run.is_empty()).map(|run| run.len()).collect::<Vec<_>>();
+            damage_groups == record.damage_groups
+        }).count()
+    }
+
+    #[test]
+    fn should_count_like_brute_force_for_any_copies() {
+        let records = ["???.### 1,1,3", ".??..??...?##. 1,1,3", "????.#...#... 4,1,1", "?###???????? 3,2,1", "?.? 1", "#?? 2"];
+        for record in records {
+            for copies in 1..=3 {
+                let record = SpringRecord::from((record, copies));
+                if record.state_row.iter().filter(|state| **state == SpringState::Unknown).count() <= 16 {
+                    assert_eq!(record.find_valid_replacements_for_unknown(), brute_force_count(&record), "{}", record);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn should_count_arrangements_of_single_records() {
+        let counts = [("???.### 1,1,3", 1), (".??..??...?##. 1,1,3", 16384), ("?#?#?#?#?#?#?#? 1,3,1,6", 1),
+            ("????.#...#... 4,1,1", 16), ("????.######..#####. 1,6,5", 2500), ("?###???????? 3,2,1", 506250)];
+        for (record, count) in counts {
+            assert_eq!(SpringRecord::from((record, 5)).find_valid_replacements_for_unknown(), count);
+        }
+    }
+
+    #[test]
+    fn should_unfold_with_custom_rules() {
+        let unfolding = Unfolding::new(3).with_separator(SpringState::Operational);
+        let record = SpringRecord::from((".??..??...?##. 1,1,3", &unfolding));
+        assert_eq!(record.find_valid_replacements_for_unknown(), 4 * 4 * 4);
+
+        let unfolding = Unfolding::new(2).without_separator();
+        let record = SpringRecord::from(("???.### 1,1,3", &unfolding));
+        assert_eq!(record.to_string(), "???.###???.### 1,1,3,1,1,3,");
+
+        let unfolding = Unfolding::new(2).with_group_copies(1);
+        let record = SpringRecord::from(("??? 1", &unfolding));
+        assert_eq!(record.to_string(), "??????? 1,");
+        assert_eq!(record.find_valid_replacements_for_unknown(), 7);
+
+        let unfolding = Unfolding::new(1).with_pattern_copies(3).with_group_copies(2);
+        let record = SpringRecord::from(("?? 1", &unfolding));
+        assert_eq!(record.to_string(), "???????? 1,1,");
+        assert_eq!(record.find_valid_replacements_for_unknown(), 21);
+
+        for unfolding in [Unfolding::new(2).with_separator(SpringState::Damaged), Unfolding::new(3).without_separator().with_group_copies(2)] {
+            let record = SpringRecord::from(("?#?.?? 2,1", &unfolding));
+            assert_eq!(record.find_valid_replacements_for_unknown(), brute_force_count(&record), "{}", record);
+        }
+    }
+
+    #[test]
+    fn should_count_unfolded_arrangements() {
+        let input = "???.### 1,1,3\n.??..??...?##. 1,1,3";
+        assert_eq!(count_unfolded_arrangements(input, &Unfolding::new(5)), count_arrangements(input, 5));
+        assert_eq!(count_unfolded_arrangements(input, &Unfolding::new(2).with_separator(SpringState::Operational)), 1 + 16);
+    }
+
+    #[test]
+    fn should_enumerate_arrangements() {
+        let record = SpringRecord::from(("?###???????? 3,2,1", 1));
+        let arrangements = record.arrangements().map(|row| to_string(&row)).collect::<Vec<_>>();
+        assert_eq!(arrangements, vec![
+            ".###....##.#", ".###...##..#", ".###...##.#.", ".###..##...#", ".###..##..#.",
+            ".###..##.#..", ".###.##....#", ".###.##...#.", ".###.##..#..", ".###.##.#...",
+        ]);
+        assert_eq!(SpringRecord::from(("???.### 1,1,3", 5)).arrangements().count(), 1);
+        assert_eq!(SpringRecord::from(("#.# 3", 1)).arrangements().count(), 0);
+    }
+
+    #[test]
+    fn should_find_nth_arrangement() {
+        let record = SpringRecord::from((".??..??...?##. 1,1,3", 2));
+        let arrangements = record.arrangements().collect::<Vec<_>>();
+        assert_eq!(arrangements.len(), record.find_valid_replacements_for_unknown());
+        for (index, arrangement) in arrangements.iter().enumerate() {
+            assert_eq!(record.nth_arrangement(index).as_ref(), Some(arrangement));
+        }
+        assert_eq!(record.nth_arrangement(arrangements.len()), None);
+    }
+
+    #[test]
+    fn should_sample_valid_arrangements() {
+        let record = SpringRecord::from(("?###???????? 3,2,1", 1));
+        let arrangements = record.arrangements().collect::<Vec<_>>();
+        for (numbers, index) in [(vec![0], 0), (vec![13], 3), (vec![u64::MAX - 5, 29], 9), (vec![u64::MAX, u64::MAX - 1, 4], 4)] {
+            let mut numbers = numbers.into_iter();
+            let sample = record.sample_arrangement(|| numbers.next().unwrap());
+            assert_eq!(sample.as_ref(), Some(&arrangements[index]));
+            assert_eq!(numbers.next(), None);
+        }
+        assert_eq!(SpringRecord::from(("#.# 3", 1)).sample_arrangement(|| 7), None);
+    }
+}
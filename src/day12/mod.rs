@@ -1,3 +1,5 @@
+use std::hash::{BuildHasher, Hasher};
+
 mod hot_springs;
 
 pub fn part1() {
@@ -8,4 +10,21 @@ pub fn part1() {
 pub fn part2() {
     let input = std::str::from_utf8(include_bytes!("../../resource/day12/input1")).unwrap();
     println!("{}", hot_springs::count_arrangements(input, 5))
-}
\ No newline at end of file
+}
+
+pub fn arrangements(record: &str) {
+    let record = hot_springs::SpringRecord::from((record, 1));
+    for arrangement in record.arrangements() {
+        println!("{}", arrangement.iter().map(|state| state.to_string()).collect::<String>())
+    }
+}
+
+pub fn sample_arrangement(record: &str) {
+    let record = hot_springs::SpringRecord::from((record, 1));
+    // every RandomState is seeded differently, which makes its hasher a cheap source of random numbers
+    let random = || std::collections::hash_map::RandomState::new().build_hasher().finish();
+    match record.sample_arrangement(random) {
+        Some(arrangement) => println!("{}", arrangement.iter().map(|state| state.to_string()).collect::<String>()),
+        None => println!("No valid arrangement")
+    }
+}
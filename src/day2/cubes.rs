@@ -1,44 +1,149 @@
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
-pub(crate) fn fewest_cubes(input: &str) -> u32 {
-    input
-        .lines()
-        .map(|line| line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Game {
+    pub(crate) id: u32,
+    pub(crate) rounds: Vec<Round>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Round {
+    pub(crate) cube_sets: Vec<CubeSet>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CubeSet {
+    pub(crate) count: u32,
+    pub(crate) color: String
+}
+
+impl Game {
+    pub(crate) fn max_per_color(&self) -> HashMap<&str, u32> {
+        let mut maxima = HashMap::new();
+        for round in &self.rounds {
+            for (color, count) in round.color_counts() {
+                let entry = maxima.entry(color).or_insert(0u32);
+                *entry = (*entry).max(count);
+            }
+        }
+        maxima
+    }
+
+    pub(crate) fn power(&self) -> u32 {
+        let maxima = self.max_per_color();
+        ["red", "green", "blue"]
+            .iter()
+            .map(|color| *maxima.get(color).unwrap_or(&0))
+            .product()
+    }
+}
+
+impl Round {
+    /// Sums up cubes of the same color, in case a color is listed multiple times within a round.
+    pub(crate) fn color_counts(&self) -> HashMap<&str, u32> {
+        let mut color_counts = HashMap::new();
+        for cube_set in &self.cube_sets {
+            let entry = color_counts.entry(cube_set.color.as_str()).or_insert(0u32);
+            *entry += cube_set.count;
+        }
+        color_counts
+    }
+}
+
+impl FromStr for Game {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let (id, content) = line
             .trim()
-            .strip_prefix("Game ").expect("Invalid line format")
+            .strip_prefix("Game ")
+            .ok_or_else(|| format!("Invalid line format '{}'", line))?
             .split_once(':')
-            .map(|(line_num, content)| (line_num.parse::<u32>().unwrap(), content))
-            .expect("Invalid line numbering"))
-        .filter_map(|(_, content)| valid_game(content))
-        .sum()
+            .ok_or_else(|| format!("Invalid line numbering '{}'", line))?;
+        let id = id.parse::<u32>().map_err(|error| format!("Invalid game id '{}': {}", id, error))?;
+        let rounds = content
+            .split(';')
+            .map(|round| round.parse::<Round>())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Game { id, rounds })
+    }
 }
 
-fn valid_game(line: &str) -> Option<u32> {
-    let mut max_red = 0;
-    let mut max_green = 0;
-    let mut max_blue = 0;
-    for round in line.split(';').map(|round| round.trim()) {
-        let mut color_counts = HashMap::new();
-        for cube_set in round.split(',').map(|cube_set| cube_set.trim()) {
-            let (num, color) = cube_set.split_once(' ').expect("Invalid cube set");
-            let num = num.parse::<u32>().unwrap();
-            let entry = color_counts.entry(color).or_insert(0u32);
-            *entry += num;
+impl FromStr for Round {
+    type Err = String;
+
+    fn from_str(round: &str) -> Result<Self, Self::Err> {
+        let cube_sets = round
+            .split(',')
+            .map(|cube_set| cube_set.parse::<CubeSet>())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Round { cube_sets })
+    }
+}
+
+impl FromStr for CubeSet {
+    type Err = String;
+
+    fn from_str(cube_set: &str) -> Result<Self, Self::Err> {
+        let (count, color) = cube_set
+            .trim()
+            .split_once(' ')
+            .ok_or_else(|| format!("Invalid cube set '{}'", cube_set))?;
+        let count = count.parse::<u32>().map_err(|error| format!("Invalid cube count '{}': {}", count, error))?;
+        Ok(CubeSet { count, color: color.trim().to_string() })
+    }
+}
+
+impl Display for Game {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Game {}: ", self.id)?;
+        for (index, round) in self.rounds.iter().enumerate() {
+            if index > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", round)?;
         }
-        let red = *color_counts.entry("red").or_insert(0);
-        let green = *color_counts.entry("green").or_insert(0);
-        let blue = *color_counts.entry("blue").or_insert(0);
+        Ok(())
+    }
+}
+
+impl Display for Round {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (index, cube_set) in self.cube_sets.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", cube_set)?;
+        }
+        Ok(())
+    }
+}
 
-        max_red = max_red.max(red);
-        max_green = max_green.max(green);
-        max_blue = max_blue.max(blue);
+impl Display for CubeSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.count, self.color)
     }
-    Some(max_blue * max_green * max_red)
+}
+
+pub(crate) fn parse_games(input: &str) -> Vec<Game> {
+    input
+        .lines()
+        .map(|line| line.parse::<Game>().unwrap())
+        .collect()
+}
+
+pub(crate) fn fewest_cubes(input: &str) -> u32 {
+    parse_games(input)
+        .iter()
+        .map(Game::power)
+        .sum()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day2::cubes::fewest_cubes;
+    use crate::day2::cubes::{fewest_cubes, Game};
 
     #[test]
     fn should_check_fewest_cubes() {
@@ -50,4 +155,16 @@ Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green"#;
 
         assert_eq!(fewest_cubes(test_input), 2286);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn should_parse_and_print_game() {
+        let line = "Game 12: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 purple";
+        let game = line.parse::<Game>().unwrap();
+
+        assert_eq!(game.id, 12);
+        assert_eq!(game.rounds.len(), 3);
+        assert_eq!(game.max_per_color().get("blue"), Some(&6));
+        assert_eq!(game.max_per_color().get("purple"), Some(&2));
+        assert_eq!(game.to_string(), line);
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 #[derive(Debug, PartialEq)]
 struct NumberSpan {
     line: usize,
@@ -18,6 +20,7 @@ enum Status {
 struct Symbol {
     line: usize,
     position: usize,
+    symbol: char,
 }
 
 impl NumberSpan {
@@ -62,25 +65,114 @@ impl NumberSpan {
     }
 }
 
+/// Bipartite graph between symbols and the number spans touching them. Symbols are indexed by
+/// their coordinates, so building the graph only inspects the cells surrounding each span.
+struct SchematicGraph {
+    spans: Vec<NumberSpan>,
+    symbols: Vec<Symbol>,
+    symbol_index: HashMap<(usize, usize), usize>,
+    symbol_to_spans: Vec<Vec<usize>>,
+    span_to_symbols: Vec<Vec<usize>>,
+}
+
 impl Symbol {
-    fn is_adjacent_to(&self, span: &NumberSpan) -> bool {
-        let line_diff = self.line.abs_diff(span.line);
-        if !(line_diff == 0 || line_diff == 1) {
-            return false;
-        }
+    fn is_gear(&self) -> bool {
+        self.symbol == '*'
+    }
+}
 
-        let start_diff = self.position.abs_diff(span.start);
-        let end_diff = self.position.abs_diff(span.end);
+impl SchematicGraph {
+    fn new(spans: Vec<NumberSpan>, symbols: Vec<Symbol>) -> Self {
+        let symbol_index = symbols
+            .iter()
+            .enumerate()
+            .map(|(index, symbol)| ((symbol.line, symbol.position), index))
+            .collect::<HashMap<_, _>>();
 
-        if !(start_diff == 0 || start_diff == 1) && !(end_diff == 0 || end_diff == 1) {
-            return false;
+        let mut symbol_to_spans = vec![Vec::new(); symbols.len()];
+        let mut span_to_symbols = vec![Vec::new(); spans.len()];
+        for (span_index, span) in spans.iter().enumerate() {
+            for line in span.line.saturating_sub(1)..=span.line + 1 {
+                for position in span.start.saturating_sub(1)..=span.end + 1 {
+                    if let Some(&symbol_index) = symbol_index.get(&(line, position)) {
+                        symbol_to_spans[symbol_index].push(span_index);
+                        span_to_symbols[span_index].push(symbol_index);
+                    }
+                }
+            }
         }
 
-        true
+        SchematicGraph { spans, symbols, symbol_index, symbol_to_spans, span_to_symbols }
+    }
+
+    fn numbers_touching_symbol_at(&self, line: usize, position: usize) -> Vec<u32> {
+        self.symbol_index
+            .get(&(line, position))
+            .map(|&symbol_index| self.symbol_to_spans[symbol_index]
+                .iter()
+                .map(|&span_index| self.spans[span_index].value)
+                .collect())
+            .unwrap_or_default()
+    }
+
+    fn symbols_touching_number(&self, value: u32) -> Vec<&Symbol> {
+        self.spans
+            .iter()
+            .enumerate()
+            .filter(|(_, span)| span.value == value)
+            .flat_map(|(span_index, _)| self.span_to_symbols[span_index].iter())
+            .map(|&symbol_index| &self.symbols[symbol_index])
+            .collect()
+    }
+
+    fn gears_with_neighbours(&self, count: usize) -> Vec<(&Symbol, Vec<&NumberSpan>)> {
+        self.symbols
+            .iter()
+            .zip(self.symbol_to_spans.iter())
+            .filter(|(symbol, adj_spans)| symbol.is_gear() && adj_spans.len() == count)
+            .map(|(symbol, adj_spans)| (symbol, adj_spans.iter().map(|&span_index| &self.spans[span_index]).collect()))
+            .collect()
+    }
+
+    fn part_numbers(&self) -> impl Iterator<Item=&NumberSpan> {
+        self.spans
+            .iter()
+            .zip(self.span_to_symbols.iter())
+            .filter(|(_, adj_symbols)| !adj_symbols.is_empty())
+            .map(|(span, _)| span)
+    }
+
+    fn unconnected_numbers(&self) -> Vec<&NumberSpan> {
+        self.spans
+            .iter()
+            .zip(self.span_to_symbols.iter())
+            .filter(|(_, adj_symbols)| adj_symbols.is_empty())
+            .map(|(span, _)| span)
+            .collect()
     }
 }
 
+pub(crate) fn part_numbers(input: &str) -> u32 {
+    build_graph(input)
+        .part_numbers()
+        .map(|span| span.value)
+        .sum()
+}
+
 pub(crate) fn gear_ratio(input: &str) -> u32 {
+    build_graph(input)
+        .gears_with_neighbours(2)
+        .into_iter()
+        .map(|(_, adj_spans)| adj_spans[0].value * adj_spans[1].value)
+        .sum()
+}
+
+fn build_graph(input: &str) -> SchematicGraph {
+    let (spans, symbols) = parse_schematic(input);
+    SchematicGraph::new(spans, symbols)
+}
+
+fn parse_schematic(input: &str) -> (Vec<NumberSpan>, Vec<Symbol>) {
     input
         .lines().enumerate()
         .map(|(line_num, line)| process_line(line.trim(), line_num))
@@ -89,9 +181,7 @@ pub(crate) fn gear_ratio(input: &str) -> u32 {
             lhs_symbol.extend(rhs_symbol);
             (lhs_span, lhs_symbol)
         })
-        .into_iter()
-        .flat_map(|(spans, symbols)| filter_number_spans(symbols, spans))
-        .sum()
+        .unwrap_or_default()
 }
 
 fn process_line(line: &str, line_num: usize) -> (Vec<NumberSpan>, Vec<Symbol>) {
@@ -102,11 +192,10 @@ fn process_line(line: &str, line_num: usize) -> (Vec<NumberSpan>, Vec<Symbol>) {
         match ch {
             '0'..='9' => active_span.set_position(position),
             '.' => active_span.finish(line),
-            '*' => {
-                symbols.push(Symbol { line: line_num, position });
+            symbol => {
+                symbols.push(Symbol { line: line_num, position, symbol });
                 active_span.finish(line)
             }
-            _ => ()
         }
 
         if active_span.is_complete() {
@@ -124,28 +213,11 @@ fn process_line(line: &str, line_num: usize) -> (Vec<NumberSpan>, Vec<Symbol>) {
     (number_spans, symbols)
 }
 
-fn filter_number_spans(symbols: Vec<Symbol>, spans: Vec<NumberSpan>) -> Vec<u32> {
-    let mut adjacent_number = Vec::new();
-    symbols
-        .iter()
-        .map(|symbol| spans.iter().fold(Vec::new(), |mut adj_numbers: Vec<u32>, span| {
-            if symbol.is_adjacent_to(span) {
-                adj_numbers.push(span.value);
-            }
-            adj_numbers
-        }))
-        .filter(|adj_numbers| adj_numbers.len() == 2)
-        .for_each(|adj_numbers| adjacent_number.push(adj_numbers[0] * adj_numbers[1]));
-    adjacent_number
-}
-
 #[cfg(test)]
 mod tests {
-    use crate::day3::gears::{gear_ratio, NumberSpan, process_line, Status, Symbol};
+    use crate::day3::gears::{build_graph, gear_ratio, NumberSpan, part_numbers, process_line, Status, Symbol};
 
-    #[test]
-    fn should_count_adjacent_numbers() {
-        let input = r#"467..114..
+    const INPUT: &str = r#"467..114..
 ...*......
 ..35..633.
 ......#...
@@ -155,7 +227,15 @@ mod tests {
 ......755.
 ...$.*....
 .664.598.."#;
-        assert_eq!(gear_ratio(input), 467835);
+
+    #[test]
+    fn should_sum_part_numbers() {
+        assert_eq!(part_numbers(INPUT), 4361);
+    }
+
+    #[test]
+    fn should_count_adjacent_numbers() {
+        assert_eq!(gear_ratio(INPUT), 467835);
     }
 
     #[test]
@@ -167,6 +247,20 @@ mod tests {
         assert_eq!(spans[0], NumberSpan { line: 42, start: 0, end: 2, value: 467, status: Status::Complete });
         assert_eq!(spans[1], NumberSpan { line: 42, start: 8, end: 9, value: 11, status: Status::Complete });
 
-        assert_eq!(symbols[0], Symbol { line: 42, position: 4 })
+        assert_eq!(symbols[0], Symbol { line: 42, position: 4, symbol: '#' })
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn should_query_schematic_graph() {
+        let graph = build_graph(INPUT);
+
+        assert_eq!(graph.numbers_touching_symbol_at(1, 3), vec![467, 35]);
+        assert_eq!(graph.numbers_touching_symbol_at(0, 0), Vec::<u32>::new());
+        assert_eq!(graph.symbols_touching_number(617), vec![&Symbol { line: 4, position: 3, symbol: '*' }]);
+        assert_eq!(graph.gears_with_neighbours(1).len(), 1);
+        assert_eq!(
+            graph.unconnected_numbers().iter().map(|span| span.value).collect::<Vec<_>>(),
+            vec![114, 58]
+        );
+    }
+}
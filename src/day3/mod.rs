@@ -1,6 +1,11 @@
 mod gears;
 
 pub fn part1() {
+    let input = std::str::from_utf8(include_bytes!("../../resource/day3/input1")).unwrap();
+    println!("{}", gears::part_numbers(input));
+}
+
+pub fn part2() {
     let input = std::str::from_utf8(include_bytes!("../../resource/day3/input1")).unwrap();
     println!("{}", gears::gear_ratio(input));
-}
\ No newline at end of file
+}
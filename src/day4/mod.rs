@@ -7,5 +7,11 @@ pub fn part1() {
 
 pub fn part2() {
     let input = std::str::from_utf8(include_bytes!("../../resource/day4/input1")).unwrap();
-    println!("{}", scratch_cards::count_games(input));
-}
\ No newline at end of file
+    println!("{}", scratch_cards::count_games(input).unwrap());
+}
+
+pub fn report() {
+    let input = std::str::from_utf8(include_bytes!("../../resource/day4/input1")).unwrap();
+    let reports = scratch_cards::card_reports(input).unwrap();
+    println!("{}", scratch_cards::reports_to_table(&reports));
+}
@@ -1,22 +1,105 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+struct ScratchCard {
+    number: u32,
+    winning_numbers: HashSet<u32>,
+    game_numbers: Vec<u32>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct CardReport {
+    pub(crate) card_number: u32,
+    pub(crate) matching_numbers: Vec<u32>,
+    pub(crate) points: u32,
+    pub(crate) copies: u128,
+    pub(crate) copied_from: Vec<u32>,
+}
+
+impl ScratchCard {
+    fn matching_numbers(&self) -> Vec<u32> {
+        self.game_numbers.iter().filter(|num| self.winning_numbers.contains(num)).copied().collect()
+    }
+}
 
 pub(crate) fn winning_points(input: &str) -> u32 {
     preprocess_input(input)
-        .into_iter()
-        .map(|(win_numbers, game)| score_scratch_card(win_numbers, game))
+        .iter()
+        .map(score_scratch_card)
         .sum()
 }
 
-pub(crate) fn count_games(input: &str) -> u32 {
-    count_cards_with_copies(preprocess_input(input))
+pub(crate) fn count_games(input: &str) -> Result<u128, String> {
+    count_cards_with_copies(&preprocess_input(input))
+}
+
+pub(crate) fn card_reports(input: &str) -> Result<Vec<CardReport>, String> {
+    let cards = preprocess_input(input);
+    let copies = copies_per_card(&cards)?;
+    let matches = cards.iter().map(|card| card.matching_numbers().len()).collect::<Vec<_>>();
+
+    Ok(cards
+        .iter()
+        .enumerate()
+        .map(|(index, card)| CardReport {
+            card_number: card.number,
+            matching_numbers: card.matching_numbers(),
+            points: score_scratch_card(card),
+            copies: copies[index],
+            copied_from: (0..index)
+                .filter(|&source| source + matches[source] >= index)
+                .map(|source| cards[source].number)
+                .collect(),
+        })
+        .collect())
+}
+
+pub(crate) fn reports_to_table(reports: &[CardReport]) -> String {
+    let mut table = String::new();
+    writeln!(table, "{:>6} | {:<30} | {:>6} | {:>8} | copied from", "card", "matching", "points", "copies").unwrap();
+    for report in reports {
+        writeln!(
+            table,
+            "{:>6} | {:<30} | {:>6} | {:>8} | {}",
+            report.card_number,
+            join_numbers(&report.matching_numbers, " "),
+            report.points,
+            report.copies,
+            join_numbers(&report.copied_from, " ")
+        ).unwrap();
+    }
+    table
+}
+
+pub(crate) fn reports_to_json(reports: &[CardReport]) -> String {
+    let entries = reports
+        .iter()
+        .map(|report| format!(
+            r#"{{"card":{},"matching":[{}],"points":{},"copies":{},"copied_from":[{}]}}"#,
+            report.card_number,
+            join_numbers(&report.matching_numbers, ","),
+            report.points,
+            report.copies,
+            join_numbers(&report.copied_from, ",")
+        ))
+        .collect::<Vec<_>>();
+    format!("[{}]", entries.join(","))
+}
+
+fn join_numbers(numbers: &[u32], separator: &str) -> String {
+    numbers.iter().map(|num| num.to_string()).collect::<Vec<_>>().join(separator)
 }
 
-fn preprocess_input(input: &str) -> Vec<(HashSet<u32>, Vec<u32>)> {
+fn preprocess_input(input: &str) -> Vec<ScratchCard> {
     input
         .lines()
-        .map(|line| line.split_once(':').expect("Invalid line format").1)
-        .map(|line| line.trim().split_once('|').expect("Invalid line format"))
-        .map(|(win_numbers, game)| (number_str_to_set(win_numbers), number_str_to_vec(game)))
+        .map(|line| line.split_once(':').expect("Invalid line format"))
+        .map(|(card, line)| (card, line.trim().split_once('|').expect("Invalid line format")))
+        .map(|(card, (win_numbers, game))| ScratchCard {
+            number: card.trim_start_matches("Card").trim().parse::<u32>().expect("Invalid card number"),
+            winning_numbers: number_str_to_set(win_numbers),
+            game_numbers: number_str_to_vec(game),
+        })
         .collect::<Vec<_>>()
 }
 
@@ -34,11 +117,11 @@ fn number_str_to_vec(number_str: &str) -> Vec<u32> {
     })
 }
 
-fn score_scratch_card(winning_numbers: HashSet<u32>, game_numbers: Vec<u32>) -> u32 {
+fn score_scratch_card(card: &ScratchCard) -> u32 {
     let mut score = 0;
 
-    for num in game_numbers {
-        if winning_numbers.contains(&num) {
+    for num in &card.game_numbers {
+        if card.winning_numbers.contains(num) {
             if score == 0 {
                 score = 1;
             } else {
@@ -50,44 +133,46 @@ fn score_scratch_card(winning_numbers: HashSet<u32>, game_numbers: Vec<u32>) ->
     score
 }
 
-fn count_cards_with_copies(games: Vec<(HashSet<u32>, Vec<u32>)>) -> u32 {
-    let mut card_stack = games.iter().enumerate().collect::<Vec<_>>();
-    let mut game_counter = 0;
-    let mut game_cache = HashMap::new();
-
-    while !card_stack.is_empty() {
-        let mut copies = Vec::new();
-        for (game_num, (winning_numbers, game_numbers)) in card_stack.into_iter() {
-            game_counter += 1;
-            if !game_cache.contains_key(&game_num) {
-                let mut copy_counter = 0;
-                for num in game_numbers {
-                    if winning_numbers.contains(&num) {
-                        copy_counter += 1;
-                    }
-                }
-
-                let mut local_copies = Vec::new();
-                for copy_index in 1..=copy_counter {
-                    let copy_game_num = game_num + copy_index;
-                    local_copies.push((copy_game_num, &games[copy_game_num]))
-                }
-                game_cache.insert(game_num, local_copies);
-            }
+fn count_cards_with_copies(cards: &[ScratchCard]) -> Result<u128, String> {
+    copies_per_card(cards)?
+        .into_iter()
+        .try_fold(0u128, |card_counter, copies| card_counter.checked_add(copies))
+        .ok_or_else(|| "Card count overflow".to_string())
+}
 
-            let local_copies = game_cache.get(&game_num).unwrap().clone();
-            copies.extend(local_copies);
+/// Counts the copies held of every card in a single pass. Every card adds its number of copies to
+/// a range of following cards, which is tracked by recording where each range starts and ends.
+fn copies_per_card(cards: &[ScratchCard]) -> Result<Vec<u128>, String> {
+    let mut range_starts = vec![0u128; cards.len() + 1];
+    let mut range_ends = vec![0u128; cards.len() + 1];
+    let mut won_copies = 0u128;
+    let mut copies_per_card = Vec::with_capacity(cards.len());
+
+    for (index, card) in cards.iter().enumerate() {
+        won_copies = won_copies.checked_add(range_starts[index]).ok_or("Card count overflow")? - range_ends[index];
+        let copies = won_copies + 1;
+        copies_per_card.push(copies);
+
+        let matches = card.matching_numbers().len();
+        if matches == 0 {
+            continue;
         }
-        // add copies to stack
-        card_stack = copies;
+        if index + matches >= cards.len() {
+            return Err(format!(
+                "Card {} wins copies of the next {} cards, but the table ends at card {}",
+                card.number, matches, cards.len()
+            ));
+        }
+        range_starts[index + 1] = range_starts[index + 1].checked_add(copies).ok_or("Card count overflow")?;
+        range_ends[index + matches + 1] = range_ends[index + matches + 1].checked_add(copies).ok_or("Card count overflow")?;
     }
 
-    game_counter
+    Ok(copies_per_card)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day4::scratch_cards::{count_games, winning_points};
+    use crate::day4::scratch_cards::{card_reports, count_games, reports_to_json, reports_to_table, winning_points};
 
     #[test]
     fn should_score_cards() {
@@ -108,6 +193,31 @@ Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
 Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
 Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
 Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11"#;
-        assert_eq!(count_games(input), 30);
+        assert_eq!(count_games(input), Ok(30));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn should_fail_when_copies_exceed_table() {
+        let input = r#"Card 1: 41 48 | 41 48
+Card 2: 13 32 | 61 30"#;
+        assert!(count_games(input).is_err());
+    }
+
+    #[test]
+    fn should_report_cards() {
+        let input = r#"Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11"#;
+        let reports = card_reports(input).unwrap();
+
+        assert_eq!(reports.iter().map(|report| report.copies).collect::<Vec<_>>(), vec![1, 2, 4, 8, 14, 1]);
+        assert_eq!(reports[0].matching_numbers, vec![83, 86, 17, 48]);
+        assert_eq!(reports[0].points, 8);
+        assert_eq!(reports[4].copied_from, vec![1, 3, 4]);
+        assert_eq!(reports_to_table(&reports).lines().count(), 7);
+        assert!(reports_to_json(&reports).starts_with(r#"[{"card":1,"matching":[83,86,17,48],"points":8,"copies":1,"copied_from":[]},"#));
+    }
+}
@@ -1,16 +1,29 @@
 use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::str::FromStr;
 
-const CARDS: [char; 13] = ['J', '2', '3', '4', '5', '6', '7', '8', '9', 'T', 'Q', 'K', 'A'];
+const STANDARD_CARDS: &str = "23456789TJQKA";
+const JOKER_CARDS: &str = "J23456789TQKA";
+const MAX_CARDS: usize = 16;
 
+/// Rules of a Camel Cards game: the ranking of the cards from lowest to highest and an optional
+/// wildcard card, which acts like whatever card makes the hand type strongest.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub(crate) struct RuleSet {
+    ranking: Vec<char>,
+    wildcard: Option<char>,
+}
+
+/// A hand with its type and sort key resolved once at parse time. The sort key packs the hand type
+/// above the five card ranks, four bits each, so hands order by a single integer comparison.
 #[derive(Debug, Eq, PartialEq, Hash)]
 struct Hand {
-    cards: [char; 5]
+    cards: [char; 5],
+    hand_type: HandType,
+    sort_key: u32,
 }
 
 #[repr(u8)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 enum HandType {
     HighCard,
     OnePair,
@@ -21,139 +34,153 @@ enum HandType {
     Five,
 }
 
-pub fn score_hands(input: &str) -> u32 {
-    let hands_with_bids = input
+impl RuleSet {
+    pub(crate) fn standard() -> Self {
+        RuleSet { ranking: STANDARD_CARDS.chars().collect(), wildcard: None }
+    }
+
+    pub(crate) fn joker() -> Self {
+        RuleSet { ranking: JOKER_CARDS.chars().collect(), wildcard: Some('J') }
+    }
+
+    /// Creates rules from a user-defined ranking, given from lowest to highest card.
+    pub(crate) fn custom(ranking: &str, wildcard: Option<char>) -> Result<Self, String> {
+        let ranking = ranking.chars().collect::<Vec<_>>();
+        if let Some(duplicate) = ranking.iter().enumerate().find(|(pos, card)| ranking[..*pos].contains(card)) {
+            return Err(format!("Card '{}' is ranked more than once", duplicate.1));
+        }
+        if ranking.len() > MAX_CARDS {
+            return Err(format!("At most {} cards can be ranked, got {}", MAX_CARDS, ranking.len()));
+        }
+        if let Some(wildcard) = wildcard.filter(|wildcard| !ranking.contains(wildcard)) {
+            return Err(format!("Wildcard '{}' is not part of the ranking", wildcard));
+        }
+        Ok(RuleSet { ranking, wildcard })
+    }
+
+    fn card_rank(&self, card: char) -> Option<usize> {
+        self.ranking.iter().position(|&elem| elem == card)
+    }
+
+    /// Classifies a hand by counting its cards. Wildcards always join the largest group of
+    /// other cards, which yields the strongest possible hand type.
+    fn hand_type(&self, cards: [char; 5]) -> HandType {
+        let mut occurrences = occurrences(cards);
+        let wildcards = self.wildcard.and_then(|wildcard| occurrences.remove(&wildcard)).unwrap_or(0);
+        let mut values = occurrences.into_values().collect::<Vec<_>>();
+        values.sort_by(|a, b| b.cmp(a));
+        match values.first_mut() {
+            Some(largest) => *largest += wildcards,
+            None => values.push(wildcards)
+        }
+        hand_type_for(&values)
+    }
+}
+
+/// Scores all hands by their rank times their bid. Identical hands are kept as separate entries,
+/// and ties between them are ranked in input order.
+pub fn score_hands(input: &str, rules: &RuleSet) -> u32 {
+    let mut hands_with_bids = input
         .lines()
         .map(|line| line.split_once(' ').unwrap())
-        .map(|(hand, bid)| (Hand::from_str(hand).unwrap(), bid.parse::<u32>().unwrap()))
-        .collect::<HashMap<Hand, u32>>();
+        .map(|(hand, bid)| (Hand::parse(hand, rules).unwrap(), bid.parse::<u32>().unwrap()))
+        .collect::<Vec<_>>();
 
-    let mut hands = hands_with_bids.keys().collect::<Vec<_>>();
-    hands.sort();
-    hands.iter().enumerate().map(|(pos, hand)| {
-        let bid = hands_with_bids.get(hand).unwrap();
+    hands_with_bids.sort_by_key(|(hand, _)| hand.sort_key);
+    hands_with_bids.iter().enumerate().map(|(pos, (_, bid))| {
         (pos + 1) as u32 * bid
     }).sum()
 }
 
-impl FromStr for Hand {
-    type Err = Vec<char>;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+impl Hand {
+    fn parse(s: &str, rules: &RuleSet) -> Result<Self, Vec<char>> {
         let cards: [char;5] = s.chars().collect::<Vec<_>>().try_into()?;
-        Ok(Hand { cards })
+        let hand_type = rules.hand_type(cards);
+        let mut sort_key = hand_type as u32;
+        for card in cards {
+            let rank = rules.card_rank(card).ok_or_else(|| cards.to_vec())?;
+            sort_key = (sort_key << 4) | rank as u32;
+        }
+        Ok(Hand { cards, hand_type, sort_key })
     }
 }
 
 impl PartialOrd for Hand {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match self.hand_type().partial_cmp(&other.hand_type()) {
-            // Hand types are equal so decide ordering based on first card that differs
-            None => Some(self.cards.iter().zip(other.cards.iter()).find_map(|(lhs, rhs)| {
-                let lhs_pos = CARDS.iter().position(|&elem| elem.eq(lhs)).unwrap();
-                let rhs_pos = CARDS.iter().position(|&elem| elem.eq(rhs)).unwrap();
-                let ord = lhs_pos.cmp(&rhs_pos);
-                if ord.is_ne() {
-                    return Some(ord)
-                }
-                None
-            }).unwrap()),
-            ordering => ordering
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Hand {
+    /// Orders by hand type first and decides ties based on the first card that differs. Hands are
+    /// only meaningfully comparable if they were parsed with the same rules.
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        self.sort_key.cmp(&other.sort_key)
     }
 }
 
-impl PartialOrd for HandType {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match (self.clone() as u8).partial_cmp(&(other.clone() as u8)) {
-            // We cannot decide equality based on the hand type only
-            Some(Ordering::Equal) => None,
-            other => other
-        }
+fn occurrences(cards: [char;5]) -> HashMap<char, u32> {
+    let mut occurrences = HashMap::new();
+    for card in cards {
+        let entry = occurrences.entry(card).or_insert(0);
+        *entry += 1;
     }
+    occurrences
 }
 
-impl PartialEq for HandType {
-    fn eq(&self, other: &Self) -> bool {
-        self == other
+fn hand_type_for(group_sizes: &[u32]) -> HandType {
+    match group_sizes {
+        [5, ..] => HandType::Five,
+        [4, ..] => HandType::Four,
+        [3, 2, ..] => HandType::FullHouse,
+        [3, ..] => HandType::Three,
+        [2, 2, ..] => HandType::TwoPair,
+        [2, ..] => HandType::OnePair,
+        [..] => HandType::HighCard,
     }
 }
 
-impl Hand
-{
-    fn occurrences(cards: [char;5]) -> HashMap<char, u32> {
-        let mut occurrences = HashMap::new();
-        for card in cards {
-            let entry = occurrences.entry(card).or_insert(0);
-            *entry += 1;
-        }
-        occurrences
-    }
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use crate::day7::camel_cards::{Hand, HandType, RuleSet, score_hands};
 
-    fn hand_type(&self) -> HandType {
-        if self.cards.contains(&'J') {
-            let mut max = None;
-            for cards in self.replacements_for_joker() {
-                if max.is_none() {
-                    max = Some(cards);
-                } else {
-                    max = Some(max.unwrap().max(cards));
-                }
+    /// Every hand made of the given cards. Few distinct cards make hand type ties and wildcards
+    /// show up frequently.
+    fn all_hands(rules: &RuleSet, cards: &[char]) -> Vec<Hand> {
+        (0..cards.len().pow(5)).map(|mut index| {
+            let mut hand = String::new();
+            for _ in 0..5 {
+                hand.push(cards[index % cards.len()]);
+                index /= cards.len();
             }
-            return Self::hand_type_for(&max.unwrap());
-        }
-        Self::hand_type_for(self)
+            Hand::parse(&hand, rules).unwrap()
+        }).collect()
     }
 
-    fn hand_type_for(hand: &Hand) -> HandType {
-        let occurrences = Self::occurrences(hand.cards);
-        let mut values = occurrences.values().filter(|value| **value != 0).collect::<Vec<_>>();
-        values.sort_by(|a, b| b.cmp(a));
-        match values[..] {
-            [5, ..] => HandType::Five,
-            [4, ..] => HandType::Four,
-            [3, 2, ..] => HandType::FullHouse,
-            [3, ..] => HandType::Three,
-            [2, 2, ..] => HandType::TwoPair,
-            [2, ..] => HandType::OnePair,
-            [..] => HandType::HighCard,
-        }
-    }
+    const INPUT: &str = r#"32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483"#;
 
-    fn replacements_for_joker(&self) -> Vec<Hand> {
-        let mut replacements = Vec::new();
-        for replacement in &CARDS[1..] {
-            let mut new_cards = self.cards;
-            for c in new_cards.iter_mut() {
-                if *c == 'J' {
-                    *c = *replacement
-                }
-            }
-            replacements.push(Hand{ cards: new_cards });
-        }
-        replacements
+    #[test]
+    fn should_score_hands() {
+        assert_eq!(score_hands(INPUT, &RuleSet::joker()), 5905);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::cmp::Ordering;
-    use crate::day7::camel_cards::{Hand, HandType, score_hands};
+    #[test]
+    fn should_score_hands_with_standard_rules() {
+        assert_eq!(score_hands(INPUT, &RuleSet::standard()), 6440);
+    }
 
     #[test]
-    fn should_score_hands() {
-        let input = r#"32T3K 765
-T55J5 684
-KK677 28
-KTJJT 220
-QQQJA 483"#;
-        assert_eq!(score_hands(input), 5905);
+    fn should_score_hands_with_custom_rules() {
+        let rules = RuleSet::custom("AKQJT98765432", None).unwrap();
+        assert_eq!(score_hands("AAAAK 1\nAAAA2 2", &rules), 5);
+
+        assert!(RuleSet::custom("23456789TJQKAA", None).is_err());
+        assert!(RuleSet::custom("23456789TJQKA", Some('X')).is_err());
     }
 
     #[test]
@@ -168,22 +195,85 @@ QQQJA 483"#;
 
     #[test]
     fn should_order_tie() {
-        let lhs = Hand { cards: ['Q', 'Q', 'Q', 'J', 'A'] };
-        let rhs = Hand { cards: ['T', '5', '5', 'J', '5'] };
+        let rules = RuleSet::standard();
+        let lhs = Hand::parse("QQQJA", &rules).unwrap();
+        let rhs = Hand::parse("T55J5", &rules).unwrap();
         let ordering = lhs.cmp(&rhs);
         assert_eq!(ordering, Ordering::Greater);
 
-        let lhs = Hand { cards: ['K', 'K', '6', '7', '7'] };
-        let rhs = Hand { cards: ['K', 'T', 'J', 'J', 'T'] };
+        let lhs = Hand::parse("KK677", &rules).unwrap();
+        let rhs = Hand::parse("KTJJT", &rules).unwrap();
         let ordering = lhs.cmp(&rhs);
         assert_eq!(ordering, Ordering::Greater);
     }
 
+    #[test]
+    fn should_order_tie_with_jokers() {
+        let rules = RuleSet::joker();
+        let lhs = Hand::parse("KK677", &rules).unwrap();
+        let rhs = Hand::parse("KTJJT", &rules).unwrap();
+        assert_eq!(lhs.cmp(&rhs), Ordering::Less);
+    }
+
     #[test]
     fn should_compute_max() {
-        let lhs = Hand { cards: ['T', '5', '5', '5', '5'] };
-        let rhs = Hand { cards: ['T', '5', '5', '6', '5'] };
+        let rules = RuleSet::joker();
+        let lhs = Hand::parse("T5555", &rules).unwrap();
+        let rhs = Hand::parse("T5565", &rules).unwrap();
 
-        assert_eq!(Hand { cards: ['T', '5', '5', '5', '5'] }, rhs.max(lhs));
+        assert_eq!(Hand::parse("T5555", &rules).unwrap(), rhs.max(lhs));
+    }
+
+    #[test]
+    fn should_add_jokers_to_largest_group() {
+        let rules = RuleSet::joker();
+        assert_eq!(Hand::parse("JJJJJ", &rules).unwrap().hand_type, HandType::Five);
+        assert_eq!(Hand::parse("KTJJT", &rules).unwrap().hand_type, HandType::Four);
+        assert_eq!(Hand::parse("2233J", &rules).unwrap().hand_type, HandType::FullHouse);
+        assert_eq!(Hand::parse("2345J", &rules).unwrap().hand_type, HandType::OnePair);
+        assert_eq!(Hand::parse("KTJJT", &RuleSet::standard()).unwrap().hand_type, HandType::TwoPair);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn should_order_identical_hands_as_equal() {
+        let rules = RuleSet::joker();
+        let lhs = Hand::parse("KTJJT", &rules).unwrap();
+        let rhs = Hand::parse("KTJJT", &rules).unwrap();
+
+        assert_eq!(lhs.cmp(&rhs), Ordering::Equal);
+        assert_eq!(HandType::Four.cmp(&HandType::Four), Ordering::Equal);
+    }
+
+    #[test]
+    fn should_keep_duplicate_hands() {
+        let input = "32T3K 1\nKK677 2\n32T3K 3";
+        assert_eq!(score_hands(input, &RuleSet::standard()), 1 + 2 * 3 + 3 * 2);
+    }
+
+    #[test]
+    fn should_order_hands_antisymmetric() {
+        for rules in [RuleSet::standard(), RuleSet::joker()] {
+            let hands = all_hands(&rules, &['J', '2', '9', 'A']);
+            for lhs in &hands {
+                for rhs in &hands {
+                    assert_eq!(lhs.cmp(rhs), rhs.cmp(lhs).reverse());
+                    assert_eq!(lhs.cmp(rhs) == Ordering::Equal, lhs == rhs);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn should_order_hands_transitive() {
+        for rules in [RuleSet::standard(), RuleSet::joker()] {
+            let hands = all_hands(&rules, &['J', '2', 'A']);
+            for a in &hands {
+                for b in hands.iter().filter(|b| a <= *b) {
+                    for c in hands.iter().filter(|c| b <= *c) {
+                        assert!(a <= c, "{:?} <= {:?} <= {:?}", a.cards, b.cards, c.cards);
+                    }
+                }
+            }
+        }
+    }
+}
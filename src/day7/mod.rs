@@ -1,6 +1,13 @@
 mod camel_cards;
 
+use camel_cards::RuleSet;
+
 pub fn part1() {
     let input = std::str::from_utf8(include_bytes!("../../resource/day7/input1")).unwrap();
-    println!("{}", camel_cards::score_hands(input));
-}
\ No newline at end of file
+    println!("{}", camel_cards::score_hands(input, &RuleSet::standard()));
+}
+
+pub fn part2() {
+    let input = std::str::from_utf8(include_bytes!("../../resource/day7/input1")).unwrap();
+    println!("{}", camel_cards::score_hands(input, &RuleSet::joker()));
+}
@@ -1,99 +1,371 @@
-use std::collections::HashMap;
-use std::fmt::Display;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
+use crate::day8::network_dot;
+
 enum Direction {
     Left,
     Right
 }
 
-impl From<char> for Direction {
-    fn from(c: char) -> Self {
+impl TryFrom<char> for Direction {
+    type Error = NavigationError;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
         match c {
-            'L' => Direction::Left,
-            'R' => Direction::Right,
-            _ => panic!("Unknown direction '{}'", c)
+            'L' => Ok(Direction::Left),
+            'R' => Ok(Direction::Right),
+            _ => Err(NavigationError::InvalidDirection(c))
         }
     }
 }
 
-pub fn steps_to_reach_z(input: &str) -> u32 {
-    let (directions, network) = parse_input(input);
-    follow_network(directions, network)
+#[derive(Debug, PartialEq)]
+pub enum NavigationError {
+    InvalidLine { line: usize, content: String },
+    InvalidDirection(char),
+    MissingNode { line: usize, node: String },
+    UnknownStart(String),
+    NoStartNodes,
+    GoalUnreachable(String),
+    NoCommonGoal,
 }
 
-pub fn parallel_steps_to_reach_z(input: &str) -> usize {
-    let (directions, network) = parse_input(input);
-    parallel_follow(directions, network)
+impl Display for NavigationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NavigationError::InvalidLine { line, content } => write!(f, "Invalid network line {}: '{}'", line, content),
+            NavigationError::InvalidDirection(direction) => write!(f, "Unknown direction '{}' in the instructions", direction),
+            NavigationError::MissingNode { line, node } => write!(f, "Line {} references unknown node '{}'", line, node),
+            NavigationError::UnknownStart(start) => write!(f, "Start node '{}' is not part of the network", start),
+            NavigationError::NoStartNodes => write!(f, "No node of the network matches the start selection"),
+            NavigationError::GoalUnreachable(start) => write!(f, "No goal is reachable from '{}'", start),
+            NavigationError::NoCommonGoal => write!(f, "Ghosts never reach their goals at the same time"),
+        }
+    }
 }
 
-fn parse_input(input: &str) -> (Vec<Direction>, HashMap<&str, (&str, &str)>) {
-    let (directions, network) = input.split_once("\n\n").unwrap();
+/// Selects start nodes and goal nodes of a traversal, either through closures or glob patterns
+/// where `*` matches any number of characters and `?` matches a single character.
+pub struct NetworkQuery<'q> {
+    start: Box<dyn Fn(&str) -> bool + 'q>,
+    goal: Box<dyn Fn(&str) -> bool + 'q>,
+}
 
-    let directions = directions.chars().map(Direction::from).collect::<Vec<_>>();
-    let network = network.lines()
-        .map(|line| line.split_once(" = ").unwrap())
-        .map(|(source, options)| (
-            source,
-            options
-                .strip_prefix('(').unwrap()
-                .strip_suffix(')').unwrap()
-                .split_once(", ").unwrap()
-        )).collect::<HashMap<&str, (&str, &str)>>();
-    (directions, network)
+#[derive(Debug, PartialEq)]
+pub struct QueryResult {
+    pub steps_per_start: Vec<(String, Result<u32, NavigationError>)>,
+    pub synchronised_steps: Result<usize, NavigationError>,
 }
 
+impl<'q> NetworkQuery<'q> {
+    pub fn new(start: impl Fn(&str) -> bool + 'q, goal: impl Fn(&str) -> bool + 'q) -> Self {
+        NetworkQuery { start: Box::new(start), goal: Box::new(goal) }
+    }
+
+    pub fn from_globs(start: &'q str, goal: &'q str) -> Self {
+        Self::new(move |node| glob_matches(start, node), move |node| glob_matches(goal, node))
+    }
+
+    fn start_nodes<'a>(&self, network: &Network<'a>) -> Vec<&'a str> {
+        let mut start_nodes = network.keys().filter(|node| (self.start)(node)).copied().collect::<Vec<_>>();
+        start_nodes.sort();
+        start_nodes
+    }
+}
+
+/// Maps every node to its left and right neighbour.
+pub(crate) type Network<'a> = HashMap<&'a str, (&'a str, &'a str)>;
+
 const START: &str = "AAA";
 const GOAL: &str = "ZZZ";
-fn follow_network(directions: Vec<Direction>, network: HashMap<&str, (&str, &str)>) -> u32 {
-    solve_single_start(&directions, &network, START, |s| s == GOAL)
+
+pub fn steps_to_reach_z(input: &str) -> Result<u32, NavigationError> {
+    let (directions, network) = parse_input(input)?;
+    solve_single_start(&directions, &network, START, &|s| s == GOAL)
 }
 
-fn parallel_follow(directions: Vec<Direction>, network: HashMap<&str, (&str, &str)>) -> usize {
-    let mut start_positions = network.keys().filter(|node| node.ends_with('A')).copied().collect::<Vec<_>>();
+pub fn parallel_steps_to_reach_z(input: &str) -> Result<usize, NavigationError> {
+    let (directions, network) = parse_input(input)?;
+    let query = NetworkQuery::from_globs("*A", "*Z");
+    parallel_follow(&directions, &network, &query)
+}
 
-    let steps: Vec<usize> = start_positions.iter().map(|start_position| {
-        // paths to goals repeat after the first iteration, so no need to traverse any farther
-        solve_single_start(&directions, &network, start_position, |s| s.ends_with('Z')) as usize
-    }).collect();
+/// Runs a query against the network, resolving the steps each start node needs on its own as
+/// well as the number of steps until all of them are on a goal at the same time.
+pub fn query_network(input: &str, query: &NetworkQuery) -> Result<QueryResult, NavigationError> {
+    let (directions, network) = parse_input(input)?;
+    let steps_per_start = query
+        .start_nodes(&network)
+        .into_iter()
+        .map(|start| (start.to_string(), solve_single_start(&directions, &network, start, &query.goal)))
+        .collect();
+    let synchronised_steps = parallel_follow(&directions, &network, query);
+    Ok(QueryResult { steps_per_start, synchronised_steps })
+}
 
-    steps.into_iter().reduce(|a, b| (a * b) / gdc(a, b)).unwrap()
+/// Exports the network as DOT, optionally coloring the path each ghost takes until it reaches
+/// a goal or starts looping.
+pub fn network_to_dot(input: &str, with_ghost_paths: bool) -> Result<String, NavigationError> {
+    let (directions, network) = parse_input(input)?;
+    let ghost_paths = if with_ghost_paths {
+        let query = NetworkQuery::from_globs("*A", "*Z");
+        query
+            .start_nodes(&network)
+            .into_iter()
+            .map(|start_position| ghost_path(&directions, &network, start_position, &query.goal))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    Ok(network_dot::to_dot(&network, &ghost_paths))
 }
 
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    // matches[j] tells whether the pattern processed so far matches the first j characters of text
+    let mut matches = vec![false; text.len() + 1];
+    matches[0] = true;
+    for token in pattern {
+        let previous = matches.clone();
+        matches[0] = token == '*' && previous[0];
+        for j in 1..=text.len() {
+            matches[j] = match token {
+                '*' => previous[j] || matches[j - 1],
+                '?' => previous[j - 1],
+                c => previous[j - 1] && text[j - 1] == c
+            };
+        }
+    }
+    matches[text.len()]
+}
+
+/// Parses the instructions and the network. Every node referenced on the right hand side has to
+/// be defined somewhere in the network, so that traversals never run into unknown nodes.
+fn parse_input(input: &str) -> Result<(Vec<Direction>, Network<'_>), NavigationError> {
+    let (directions, network) = input
+        .split_once("\n\n")
+        .ok_or_else(|| NavigationError::InvalidLine { line: 1, content: input.lines().next().unwrap_or_default().to_string() })?;
+
+    if directions.is_empty() {
+        return Err(NavigationError::InvalidLine { line: 1, content: directions.to_string() });
+    }
+    let directions = directions.chars().map(Direction::try_from).collect::<Result<Vec<_>, _>>()?;
+    // the network starts after the instruction line and the empty separator line
+    let first_line = 3;
+    let nodes = network.lines()
+        .enumerate()
+        .map(|(index, line)| parse_node(line)
+            .map(|node| (index + first_line, node))
+            .ok_or_else(|| NavigationError::InvalidLine { line: index + first_line, content: line.to_string() }))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let network = nodes.iter().map(|(_, node)| *node).collect::<Network>();
+    for (line, (_, (left, right))) in &nodes {
+        if let Some(missing) = [left, right].into_iter().find(|target| !network.contains_key(*target)) {
+            return Err(NavigationError::MissingNode { line: *line, node: missing.to_string() });
+        }
+    }
+    Ok((directions, network))
+}
+
+fn parse_node(line: &str) -> Option<(&str, (&str, &str))> {
+    let (source, options) = line.split_once(" = ")?;
+    let options = options
+        .strip_prefix('(')?
+        .strip_suffix(')')?
+        .split_once(", ")?;
+    Some((source, options))
+}
+
+/// Goals visited by a single ghost. After `tail_length` steps the ghost is caught in a cycle of
+/// `cycle_length` steps, so every goal in `cycle_goals` is visited again each cycle, whereas the
+/// goals in `tail_goals` are only visited once.
+#[derive(Debug, PartialEq)]
+struct CycleInfo {
+    tail_length: usize,
+    cycle_length: usize,
+    tail_goals: Vec<usize>,
+    cycle_goals: Vec<usize>,
+}
+
+impl CycleInfo {
+    fn reaches_goal_at(&self, step: usize) -> bool {
+        if step < self.tail_length {
+            return self.tail_goals.contains(&step);
+        }
+        let offset = (step - self.tail_length) % self.cycle_length;
+        self.cycle_goals.contains(&(self.tail_length + offset))
+    }
+}
+
+fn parallel_follow(
+    directions: &[Direction],
+    network: &Network,
+    query: &NetworkQuery
+) -> Result<usize, NavigationError> {
+    let start_positions = query.start_nodes(network);
+    if start_positions.is_empty() {
+        return Err(NavigationError::NoStartNodes);
+    }
+
+    let cycles = start_positions
+        .iter()
+        .map(|start_position| analyse_cycle(directions, network, start_position, &query.goal))
+        .collect::<Vec<_>>();
+
+    first_common_goal(&cycles).ok_or(NavigationError::NoCommonGoal)
+}
+
+/// Walks the network until a (node, instruction index) state repeats, which means the ghost will
+/// loop forever from there on.
+fn analyse_cycle(
+    directions: &[Direction],
+    network: &Network,
+    start: &str,
+    predicate: &dyn Fn(&str) -> bool
+) -> CycleInfo {
+    let mut visited = HashMap::new();
+    let mut goals = Vec::new();
+    let mut node = start;
+    let mut step = 0;
+    loop {
+        let instruction = step % directions.len();
+        if let Some(&first_visit) = visited.get(&(node, instruction)) {
+            // step 0 does not count as reaching a goal, but once the ghost is back at its start
+            // state, a goal start is reached again every cycle
+            if first_visit == 0 && predicate(node) {
+                goals.push(0);
+            }
+            let (tail_goals, cycle_goals) = goals.iter().partition(|&&goal| goal < first_visit);
+            return CycleInfo { tail_length: first_visit, cycle_length: step - first_visit, tail_goals, cycle_goals };
+        }
+        visited.insert((node, instruction), step);
+        if step > 0 && predicate(node) {
+            goals.push(step);
+        }
+
+        let next_elements = &network[node];
+        node = match directions[instruction] {
+            Direction::Left => next_elements.0,
+            Direction::Right => next_elements.1
+        };
+        step += 1;
+    }
+}
+
+/// Collects the edges a ghost takes until it reaches a goal or a (node, instruction index) state repeats.
+fn ghost_path<'a>(
+    directions: &[Direction],
+    network: &Network<'a>,
+    start: &'a str,
+    predicate: &dyn Fn(&str) -> bool
+) -> Vec<(&'a str, char)> {
+    let mut visited = HashSet::new();
+    let mut path = Vec::new();
+    let mut node = start;
+    let mut step = 0;
+    while visited.insert((node, step % directions.len())) {
+        let next_elements = &network[node];
+        let (label, next) = match directions[step % directions.len()] {
+            Direction::Left => ('L', next_elements.0),
+            Direction::Right => ('R', next_elements.1)
+        };
+        path.push((node, label));
+        if predicate(next) {
+            break;
+        }
+        node = next;
+        step += 1;
+    }
+    path
+}
+
+/// Finds the first step at which all ghosts are on a goal at the same time. Before every ghost has
+/// entered its cycle the steps are checked one by one, afterwards the goal offsets of all cycles
+/// are combined using the chinese remainder theorem.
+fn first_common_goal(cycles: &[CycleInfo]) -> Option<usize> {
+    let settled = cycles.iter().map(|cycle| cycle.tail_length).max()?;
+    if let Some(step) = (1..settled).find(|&step| cycles.iter().all(|cycle| cycle.reaches_goal_at(step))) {
+        return Some(step);
+    }
+
+    let mut congruences = vec![(0i128, 1i128)];
+    for cycle in cycles {
+        let cycle_length = cycle.cycle_length as i128;
+        congruences = congruences
+            .iter()
+            .flat_map(|&congruence| cycle.cycle_goals
+                .iter()
+                .filter_map(move |&goal| crt(congruence, (goal as i128 % cycle_length, cycle_length))))
+            .collect();
+        congruences.sort();
+        congruences.dedup();
+    }
+
+    let settled = settled.max(1) as i128;
+    congruences
+        .into_iter()
+        .map(|(residue, modulus)| residue + (settled - residue + modulus - 1).div_euclid(modulus).max(0) * modulus)
+        .min()
+        .map(|step| step as usize)
+}
+
+/// Combines two congruences `x = a (mod m)` into one, if there is any common solution.
+fn crt((lhs_residue, lhs_modulus): (i128, i128), (rhs_residue, rhs_modulus): (i128, i128)) -> Option<(i128, i128)> {
+    let (gcd, inverse, _) = extended_gcd(lhs_modulus, rhs_modulus);
+    let difference = rhs_residue - lhs_residue;
+    if difference % gcd != 0 {
+        return None;
+    }
+    let modulus = lhs_modulus / gcd * rhs_modulus;
+    let factor = (difference / gcd * inverse).rem_euclid(rhs_modulus / gcd);
+    Some(((lhs_residue + lhs_modulus * factor).rem_euclid(modulus), modulus))
+}
+
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        return (a, 1, 0);
+    }
+    let (gcd, x, y) = extended_gcd(b, a % b);
+    (gcd, y, x - (a / b) * y)
+}
+
+/// Follows the instructions until the goal is reached. Revisiting a (node, instruction index) state
+/// means the traversal would loop forever without ever reaching the goal.
 fn solve_single_start<'a>(
     directions: &[Direction],
-    network: &HashMap<&'a str, (&'a str, &'a str)>,
+    network: &Network<'a>,
     start: &str,
-    predicate: fn(&str) -> bool
-) -> u32 {
+    predicate: &dyn Fn(&str) -> bool
+) -> Result<u32, NavigationError> {
+    let mut visited = HashSet::new();
     let mut steps_taken: u32 = 0;
-    let mut next_elements = network.get(start).unwrap();
+    let mut current = start;
+    let mut next_elements = network.get(start).ok_or_else(|| NavigationError::UnknownStart(start.to_string()))?;
     loop {
-        for direction in directions.iter() {
-            let chosen_element = match direction {
+        for (instruction, direction) in directions.iter().enumerate() {
+            if !visited.insert((current, instruction)) {
+                return Err(NavigationError::GoalUnreachable(start.to_string()));
+            }
+            current = match direction {
                 Direction::Left => next_elements.0,
                 Direction::Right => next_elements.1
             };
             steps_taken += 1;
-            if predicate(chosen_element) {
-                return steps_taken;
+            if predicate(current) {
+                return Ok(steps_taken);
             }
-            next_elements = network.get(chosen_element).unwrap();
+            next_elements = &network[current];
         }
     }
 }
 
-fn gdc(a: usize, b: usize) -> usize {
-    if b == 0 {
-        return a;
-    }
-
-    gdc(b, a % b)
-}
-
 #[cfg(test)]
 mod tests {
-    use crate::day8::haunted_wastedland::{parallel_steps_to_reach_z, steps_to_reach_z};
+    use crate::day8::haunted_wastedland::{glob_matches, NavigationError, NetworkQuery, network_to_dot, parallel_steps_to_reach_z, query_network, steps_to_reach_z};
 
     #[test]
     fn should_follow_network() {
@@ -106,7 +378,7 @@ DDD = (DDD, DDD)
 EEE = (EEE, EEE)
 GGG = (GGG, GGG)
 ZZZ = (ZZZ, ZZZ)"#;
-        assert_eq!(steps_to_reach_z(input), 2);
+        assert_eq!(steps_to_reach_z(input), Ok(2));
     }
 
     #[test]
@@ -121,6 +393,137 @@ ZZZ = (ZZZ, ZZZ)"#;
 22C = (22Z, 22Z)
 22Z = (22B, 22B)
 XXX = (XXX, XXX)"#;
-        assert_eq!(parallel_steps_to_reach_z(input), 6);
+        assert_eq!(parallel_steps_to_reach_z(input), Ok(6));
+    }
+
+    #[test]
+    fn should_follow_network_parallel_with_offset_goals() {
+        let input = r#"L
+
+11A = (11B, 11B)
+11B = (11Z, 11Z)
+11Z = (11C, 11C)
+11C = (11Z, 11Z)
+22A = (22Z, 22Z)
+22Z = (22B, 22B)
+22B = (22C, 22C)
+22C = (22Z, 22Z)"#;
+        assert_eq!(parallel_steps_to_reach_z(input), Ok(4));
+    }
+
+    #[test]
+    fn should_detect_unsolvable_parallel_network() {
+        let input = r#"L
+
+11A = (11B, 11B)
+11B = (11Z, 11Z)
+11Z = (11B, 11B)
+22A = (22Z, 22Z)
+22Z = (22B, 22B)
+22B = (22Z, 22Z)"#;
+        assert_eq!(parallel_steps_to_reach_z(input), Err(NavigationError::NoCommonGoal));
+    }
+
+    #[test]
+    fn should_color_ghost_paths_in_dot() {
+        let input = r#"LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)"#;
+        let dot = network_to_dot(input, true).unwrap();
+
+        assert!(dot.contains(r#""11B" -> "11Z" [label="R", color="blue", penwidth=2];"#));
+        assert!(dot.contains(r#""22C" -> "22Z" [label="L", color="darkorange", penwidth=2];"#));
+        assert!(dot.contains(r#""XXX" -> "XXX" [label="L"];"#));
+        assert!(!network_to_dot(input, false).unwrap().contains("penwidth"));
+    }
+
+    #[test]
+    fn should_detect_unreachable_goal() {
+        let input = r#"RL
+
+AAA = (BBB, BBB)
+BBB = (AAA, AAA)
+ZZZ = (ZZZ, ZZZ)"#;
+        assert_eq!(steps_to_reach_z(input), Err(NavigationError::GoalUnreachable("AAA".to_string())));
+    }
+
+    #[test]
+    fn should_report_missing_nodes() {
+        let input = r#"RL
+
+AAA = (BBB, CCC)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)"#;
+        assert_eq!(steps_to_reach_z(input), Err(NavigationError::MissingNode { line: 3, node: "CCC".to_string() }));
+
+        let input = "RL\n\nAAA = BBB";
+        assert_eq!(steps_to_reach_z(input), Err(NavigationError::InvalidLine { line: 3, content: "AAA = BBB".to_string() }));
+    }
+
+    #[test]
+    fn should_reject_empty_instructions() {
+        let input = "\n\nAAA = (ZZZ, ZZZ)\nZZZ = (ZZZ, ZZZ)";
+        let empty_instructions = NavigationError::InvalidLine { line: 1, content: String::new() };
+        assert_eq!(steps_to_reach_z(input), Err(empty_instructions));
+        assert!(parallel_steps_to_reach_z(input).is_err());
+        assert!(network_to_dot(input, true).is_err());
+    }
+
+    #[test]
+    fn should_report_unknown_directions() {
+        let input = "RLX\n\nAAA = (ZZZ, ZZZ)\nZZZ = (ZZZ, ZZZ)";
+        assert_eq!(steps_to_reach_z(input), Err(NavigationError::InvalidDirection('X')));
+    }
+
+    #[test]
+    fn should_match_globs() {
+        assert!(glob_matches("*A", "11A"));
+        assert!(glob_matches("1?Z", "11Z"));
+        assert!(glob_matches("*", ""));
+        assert!(!glob_matches("*A", "11Z"));
+        assert!(!glob_matches("1?Z", "111Z"));
+    }
+
+    #[test]
+    fn should_query_network() {
+        let input = r#"LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)"#;
+        let result = query_network(input, &NetworkQuery::from_globs("??A", "*Z")).unwrap();
+        assert_eq!(result.steps_per_start, vec![("11A".to_string(), Ok(2)), ("22A".to_string(), Ok(3))]);
+        assert_eq!(result.synchronised_steps, Ok(6));
+
+        let starts = ["22A"];
+        let query = NetworkQuery::new(|node| starts.contains(&node), |node| node == "22C");
+        let result = query_network(input, &query).unwrap();
+        assert_eq!(result.steps_per_start, vec![("22A".to_string(), Ok(2))]);
+        assert_eq!(result.synchronised_steps, Ok(2));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn should_count_start_as_goal_when_cycling_back() {
+        let input = r#"L
+
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)"#;
+        let query = NetworkQuery::new(|node| node == "22B", |node| node == "22B");
+        let result = query_network(input, &query).unwrap();
+        assert_eq!(result.steps_per_start, vec![("22B".to_string(), Ok(3))]);
+        assert_eq!(result.synchronised_steps, Ok(3));
+    }
+}
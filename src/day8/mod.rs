@@ -1,11 +1,26 @@
 mod haunted_wastedland;
+mod network_dot;
 
 pub fn part1() {
     let input = std::str::from_utf8(include_bytes!("../../resource/day8/input1")).unwrap();
-    println!("{}", haunted_wastedland::steps_to_reach_z(input));
+    match haunted_wastedland::steps_to_reach_z(input) {
+        Ok(steps) => println!("{}", steps),
+        Err(error) => println!("{}", error)
+    }
 }
 
 pub fn part2() {
     let input = std::str::from_utf8(include_bytes!("../../resource/day8/input1")).unwrap();
-    println!("{}", haunted_wastedland::parallel_steps_to_reach_z(input));
-}
\ No newline at end of file
+    match haunted_wastedland::parallel_steps_to_reach_z(input) {
+        Ok(steps) => println!("{}", steps),
+        Err(error) => println!("{}", error)
+    }
+}
+
+pub fn dot() {
+    let input = std::str::from_utf8(include_bytes!("../../resource/day8/input1")).unwrap();
+    match haunted_wastedland::network_to_dot(input, true) {
+        Ok(dot) => println!("{}", dot),
+        Err(error) => println!("{}", error)
+    }
+}
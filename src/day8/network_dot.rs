@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::day8::haunted_wastedland::Network;
+
+const PATH_COLORS: [&str; 6] = ["blue", "darkorange", "purple", "forestgreen", "brown", "deeppink"];
+
+/// Renders the network as a DOT graph. Start nodes (`..A`) and goal nodes (`..Z`) are highlighted,
+/// and every ghost path, given as the taken edges `(source, 'L' | 'R')`, is drawn in its own color.
+pub(crate) fn to_dot(network: &Network, ghost_paths: &[Vec<(&str, char)>]) -> String {
+    let mut edge_colors: HashMap<(&str, char), Vec<&str>> = HashMap::new();
+    for (ghost, path) in ghost_paths.iter().enumerate() {
+        let color = PATH_COLORS[ghost % PATH_COLORS.len()];
+        for edge in path {
+            let colors = edge_colors.entry(*edge).or_default();
+            if !colors.contains(&color) {
+                colors.push(color);
+            }
+        }
+    }
+
+    let mut nodes = network.keys().copied().collect::<Vec<_>>();
+    nodes.sort();
+
+    let mut dot = String::new();
+    writeln!(dot, "digraph network {{").unwrap();
+    for node in &nodes {
+        if node.ends_with('A') {
+            writeln!(dot, "    \"{}\" [style=filled, fillcolor=palegreen];", node).unwrap();
+        } else if node.ends_with('Z') {
+            writeln!(dot, "    \"{}\" [style=filled, fillcolor=lightcoral];", node).unwrap();
+        }
+    }
+    for node in &nodes {
+        let (left, right) = network[node];
+        for (label, target) in [('L', left), ('R', right)] {
+            match edge_colors.get(&(*node, label)) {
+                Some(colors) => writeln!(
+                    dot,
+                    "    \"{}\" -> \"{}\" [label=\"{}\", color=\"{}\", penwidth=2];",
+                    node, target, label, colors.join(":")
+                ),
+                None => writeln!(dot, "    \"{}\" -> \"{}\" [label=\"{}\"];", node, target, label)
+            }.unwrap();
+        }
+    }
+    writeln!(dot, "}}").unwrap();
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::day8::network_dot::to_dot;
+
+    #[test]
+    fn should_render_network_as_dot() {
+        let network = HashMap::from([("11A", ("11Z", "XXX")), ("11Z", ("11Z", "11Z")), ("XXX", ("XXX", "XXX"))]);
+        let dot = to_dot(&network, &[vec![("11A", 'L')]]);
+
+        assert_eq!(dot, r#"digraph network {
+    "11A" [style=filled, fillcolor=palegreen];
+    "11Z" [style=filled, fillcolor=lightcoral];
+    "11A" -> "11Z" [label="L", color="blue", penwidth=2];
+    "11A" -> "XXX" [label="R"];
+    "11Z" -> "11Z" [label="L"];
+    "11Z" -> "11Z" [label="R"];
+    "XXX" -> "XXX" [label="L"];
+    "XXX" -> "XXX" [label="R"];
+}
+"#);
+    }
+}
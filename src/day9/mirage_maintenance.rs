@@ -1,58 +1,219 @@
-pub fn extrapolate(input: &str) -> i64 {
-    do_extrapolate(input, extrapolate_from_deltas)
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, PartialEq)]
+pub enum HistoryError {
+    Empty { line: usize },
+    NotPolynomial { line: usize },
+    Overflow { line: usize },
+}
+
+impl Display for HistoryError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HistoryError::Empty { line } => write!(f, "History in line {} is empty", line),
+            HistoryError::NotPolynomial { line } => write!(f, "Differences of history in line {} never become all zero", line),
+            HistoryError::Overflow { line } => write!(f, "Extrapolation of history in line {} overflows", line),
+        }
+    }
+}
+
+pub fn extrapolate(input: &str) -> Result<i64, HistoryError> {
+    do_extrapolate(input, |history| history.len() as i128)
+}
+
+pub fn extrapolate_backwards(input: &str) -> Result<i64, HistoryError> {
+    do_extrapolate(input, |_| -1)
 }
 
-pub fn extrapolate_backwards(input: &str) -> i64 {
-    do_extrapolate(input, extrapolate_backwards_from_deltas)
+/// Evaluates the polynomial of every history at the given position, where position 0 is the first
+/// value of a history.
+pub fn extrapolate_at(input: &str, position: i128) -> Result<i128, HistoryError> {
+    let mut sum = 0i128;
+    for (line, history) in parse_histories(input) {
+        sum = evaluate_history(line, &history, position)?
+            .checked_add(sum)
+            .ok_or(HistoryError::Overflow { line })?;
+    }
+    Ok(sum)
+}
+
+/// Reports every history that cannot be extrapolated, instead of stopping at the first one.
+pub fn check_histories(input: &str) -> Vec<HistoryError> {
+    parse_histories(input)
+        .filter_map(|(line, history)| Polynomial::from_history(&history)
+            .err()
+            .map(|error| history_error(line, &history, error)))
+        .collect()
 }
 
-fn do_extrapolate(input: &str, map_fn: fn (Vec<Vec<i64>>) -> i64) -> i64 {
+fn parse_histories(input: &str) -> impl Iterator<Item=(usize, Vec<i64>)> + '_ {
     input.lines()
-        .map(|line| line.split_ascii_whitespace().map(|s| s.parse::<i64>().unwrap()).collect::<Vec<_>>())
-        .map(compute_deltas_until_all_zero)
-        .map(map_fn)
-        .sum()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.split_ascii_whitespace().map(|s| s.parse::<i64>().unwrap()).collect::<Vec<_>>()))
+}
+
+fn history_error(line: usize, history: &[i64], error: PolynomialError) -> HistoryError {
+    match error {
+        PolynomialError::NotPolynomial => invalid_history(line, history),
+        PolynomialError::Overflow => HistoryError::Overflow { line },
+    }
+}
+
+fn invalid_history(line: usize, history: &[i64]) -> HistoryError {
+    match history {
+        [] => HistoryError::Empty { line },
+        _ => HistoryError::NotPolynomial { line }
+    }
+}
+
+/// The polynomial underlying a history in Newton form, i.e. the first entry of every delta row.
+/// Its value at position `x` is the sum of `differences[k] * binomial(x, k)`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Polynomial {
+    differences: Vec<i128>,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum PolynomialError {
+    NotPolynomial,
+    Overflow,
+}
+
+/// An exact fraction, always stored with a positive denominator and in lowest terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Rational {
+    numerator: i128,
+    denominator: i128,
+}
+
+impl Polynomial {
+    /// Builds delta rows in checked `i128` arithmetic until one of them is all zero, keeping only
+    /// their first entries. Every row is one element shorter than the previous one, so a history
+    /// whose differences have not stabilised before running out of values is no polynomial (or too
+    /// short to tell). Also fails if a delta does not fit into an `i128`.
+    pub(crate) fn from_history(history: &[i64]) -> Result<Self, PolynomialError> {
+        let mut deltas = history.iter().map(|&value| value as i128).collect::<Vec<_>>();
+        let mut differences = Vec::new();
+        while !deltas.iter().all(|delta| *delta == 0) {
+            differences.push(deltas[0]);
+            deltas = deltas
+                .windows(2)
+                .map(|slice| slice[1].checked_sub(slice[0]))
+                .collect::<Option<Vec<_>>>()
+                .ok_or(PolynomialError::Overflow)?;
+        }
+        if deltas.is_empty() {
+            return Err(PolynomialError::NotPolynomial);
+        }
+        Ok(Polynomial { differences })
+    }
+
+    /// The degree of the polynomial, where the zero polynomial is treated as a constant.
+    pub(crate) fn degree(&self) -> usize {
+        self.differences.len().saturating_sub(1)
+    }
+
+    pub(crate) fn evaluate(&self, position: i128) -> Option<i128> {
+        let mut value = 0i128;
+        let mut binomial = 1i128;
+        for (k, difference) in self.differences.iter().enumerate() {
+            if k > 0 {
+                // the product of k consecutive integers is always divisible by k!
+                binomial = binomial.checked_mul(position - k as i128 + 1)? / k as i128;
+            }
+            value = value.checked_add(difference.checked_mul(binomial)?)?;
+        }
+        Some(value)
+    }
+
+    /// The coefficients in monomial form, starting with the constant term.
+    pub(crate) fn coefficients(&self) -> Option<Vec<Rational>> {
+        let mut coefficients = vec![Rational::from(0); self.differences.len().max(1)];
+        // coefficients of the falling factorial x * (x - 1) * ... * (x - k + 1)
+        let mut falling_factorial = vec![1i128];
+        let mut factorial = 1i128;
+        for (k, difference) in self.differences.iter().enumerate() {
+            if k > 0 {
+                factorial = factorial.checked_mul(k as i128)?;
+                let mut next = vec![0i128; falling_factorial.len() + 1];
+                for (power, coefficient) in falling_factorial.iter().enumerate() {
+                    next[power + 1] = next[power + 1].checked_add(*coefficient)?;
+                    next[power] = next[power].checked_sub(coefficient.checked_mul(k as i128 - 1)?)?;
+                }
+                falling_factorial = next;
+            }
+            for (power, coefficient) in falling_factorial.iter().enumerate() {
+                let term = Rational::new(difference.checked_mul(*coefficient)?, factorial);
+                coefficients[power] = coefficients[power].checked_add(term)?;
+            }
+        }
+        Some(coefficients)
+    }
 }
 
-fn extrapolate_from_deltas(all_deltas: Vec<Vec<i64>>) -> i64 {
-    all_deltas[..all_deltas.len()-1]
-        .iter()
-        .rev()
-        .fold(0, |extrapolation, deltas| extrapolation + deltas.last().unwrap())
+impl Rational {
+    pub(crate) fn new(numerator: i128, denominator: i128) -> Self {
+        let divisor = gcd(numerator, denominator) * denominator.signum();
+        Rational { numerator: numerator / divisor, denominator: denominator / divisor }
+    }
+
+    fn checked_add(self, other: Rational) -> Option<Rational> {
+        let numerator = self.numerator.checked_mul(other.denominator)?
+            .checked_add(other.numerator.checked_mul(self.denominator)?)?;
+        Some(Rational::new(numerator, self.denominator.checked_mul(other.denominator)?))
+    }
 }
 
-fn extrapolate_backwards_from_deltas(all_deltas: Vec<Vec<i64>>) -> i64 {
-    all_deltas[..all_deltas.len() - 1]
-        .iter()
-        .rev()
-        .fold(0, |extrapolation, deltas| deltas.first().unwrap() - extrapolation)
+impl From<i128> for Rational {
+    fn from(value: i128) -> Self {
+        Rational { numerator: value, denominator: 1 }
+    }
 }
 
-fn compute_deltas_until_all_zero(history: Vec<i64>) -> Vec<Vec<i64>> {
-    let mut deltas = Vec::new();
-    deltas.push(history);
+impl Display for Rational {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.denominator {
+            1 => write!(f, "{}", self.numerator),
+            _ => write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
 
-    while !deltas.last().unwrap().iter().all(|delta| *delta == 0) {
-        deltas.push(compute_deltas(deltas.last().unwrap()))
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        return a.abs();
     }
+    gcd(b, a % b)
+}
 
-    deltas
+fn do_extrapolate(input: &str, position: fn(&[i64]) -> i128) -> Result<i64, HistoryError> {
+    let mut sum = 0i64;
+    for (line, history) in parse_histories(input) {
+        sum = i64::try_from(evaluate_history(line, &history, position(&history))?)
+            .ok()
+            .and_then(|value| sum.checked_add(value))
+            .ok_or(HistoryError::Overflow { line })?;
+    }
+    Ok(sum)
 }
 
-fn compute_deltas(history: &[i64]) -> Vec<i64> {
-    history.windows(2).map(|slice| slice[1] - slice[0]).collect::<Vec<_>>()
+fn evaluate_history(line: usize, history: &[i64], position: i128) -> Result<i128, HistoryError> {
+    Polynomial::from_history(history)
+        .map_err(|error| history_error(line, history, error))?
+        .evaluate(position)
+        .ok_or(HistoryError::Overflow { line })
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day9::mirage_maintenance::{compute_deltas, extrapolate, extrapolate_backwards};
+    use crate::day9::mirage_maintenance::{check_histories, extrapolate, extrapolate_at, extrapolate_backwards, HistoryError, Polynomial, PolynomialError, Rational};
 
     #[test]
     fn should_extrapolate() {
         let input = r#"0 3 6 9 12 15
 1 3 6 10 15 21
 10 13 16 21 30 45"#;
-        assert_eq!(extrapolate(input), 114);
+        assert_eq!(extrapolate(input), Ok(114));
     }
 
     #[test]
@@ -60,12 +221,82 @@ mod tests {
         let input = r#"0 3 6 9 12 15
 1 3 6 10 15 21
 10 13 16 21 30 45"#;
-        assert_eq!(extrapolate_backwards(input), 2);
+        assert_eq!(extrapolate_backwards(input), Ok(2));
+    }
+
+    #[test]
+    fn should_extrapolate_at_arbitrary_positions() {
+        let input = r#"0 3 6 9 12 15
+1 3 6 10 15 21
+10 13 16 21 30 45"#;
+        assert_eq!(extrapolate_at(input, 6), Ok(114));
+        assert_eq!(extrapolate_at(input, -1), Ok(2));
+
+        let polynomial = Polynomial::from_history(&[1, 3, 6, 10, 15, 21]).unwrap();
+        assert_eq!(polynomial.evaluate(1_000_000), Some(500_001_500_001));
+        assert_eq!(polynomial.evaluate(-500), Some(124_251));
+    }
+
+    #[test]
+    fn should_compute_degree_and_coefficients() {
+        let polynomial = Polynomial::from_history(&[1, 3, 6, 10, 15, 21]).unwrap();
+        assert_eq!(polynomial.degree(), 2);
+        assert_eq!(
+            polynomial.coefficients(),
+            Some(vec![Rational::from(1), Rational::new(3, 2), Rational::new(1, 2)])
+        );
+
+        let polynomial = Polynomial::from_history(&[10, 13, 16, 21, 30, 45]).unwrap();
+        assert_eq!(polynomial.degree(), 3);
+        assert_eq!(
+            polynomial.coefficients().unwrap().iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+            vec!["10", "11/3", "-1", "1/3"]
+        );
+    }
+
+    #[test]
+    fn should_detect_non_polynomial_histories() {
+        let input = r#"0 3 6 9 12 15
+1 2 4 8 16 32
+
+5"#;
+        assert_eq!(extrapolate(input), Err(HistoryError::NotPolynomial { line: 2 }));
+        assert_eq!(extrapolate_at(input, 10), Err(HistoryError::NotPolynomial { line: 2 }));
+        assert_eq!(
+            check_histories(input),
+            vec![HistoryError::NotPolynomial { line: 2 }, HistoryError::Empty { line: 3 }, HistoryError::NotPolynomial { line: 4 }]
+        );
     }
 
     #[test]
-    fn should_compute_deltas() {
-        let input: Vec<i64> = vec![1, 3, 6, 10, 15, 21];
-        assert_eq!(compute_deltas(&input), vec![2, 3, 4, 5, 6]);
+    fn should_build_polynomials_from_extreme_values() {
+        let polynomial = Polynomial::from_history(&[i64::MIN + 1, 0, i64::MAX]).unwrap();
+        assert_eq!(polynomial.degree(), 1);
+        assert_eq!(polynomial.evaluate(3), Some(2 * i64::MAX as i128));
+
+        assert_eq!(Polynomial::from_history(&[i64::MAX, i64::MIN, i64::MAX]), Err(PolynomialError::NotPolynomial));
+
+        // the k-th delta of alternating extremes grows like 2^(63 + k)
+        let alternating = (0..70).map(|i| if i % 2 == 0 { i64::MAX } else { i64::MIN }).collect::<Vec<_>>();
+        assert_eq!(Polynomial::from_history(&alternating), Err(PolynomialError::Overflow));
+
+        let input = alternating.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(" ");
+        assert_eq!(extrapolate_at(&input, 0), Err(HistoryError::Overflow { line: 1 }));
+        assert_eq!(check_histories(&input), vec![HistoryError::Overflow { line: 1 }]);
+    }
+
+    #[test]
+    fn should_extrapolate_extreme_values_without_panicking() {
+        let input = "9223372036854775807 -9223372036854775808 9223372036854775807";
+        assert_eq!(extrapolate(input), Err(HistoryError::NotPolynomial { line: 1 }));
+        assert_eq!(extrapolate_backwards(input), Err(HistoryError::NotPolynomial { line: 1 }));
+
+        let input = "9223372036854775805 9223372036854775806 9223372036854775807";
+        assert_eq!(extrapolate(input), Err(HistoryError::Overflow { line: 1 }));
+        let input = "-9223372036854775808 -9223372036854775807 -9223372036854775806";
+        assert_eq!(extrapolate_backwards(input), Err(HistoryError::Overflow { line: 1 }));
+        // each extrapolation fits, but their sum does not
+        let input = "1 1 1\n9223372036854775804 9223372036854775805 9223372036854775806";
+        assert_eq!(extrapolate(input), Err(HistoryError::Overflow { line: 2 }));
     }
-}
\ No newline at end of file
+}
@@ -2,10 +2,16 @@ mod mirage_maintenance;
 
 pub fn part1() {
     let input = std::str::from_utf8(include_bytes!("../../resource/day9/input1")).unwrap();
-    println!("{}", mirage_maintenance::extrapolate(input));
+    match mirage_maintenance::extrapolate(input) {
+        Ok(extrapolation) => println!("{}", extrapolation),
+        Err(error) => println!("{}", error)
+    }
 }
 
 pub fn part2() {
     let input = std::str::from_utf8(include_bytes!("../../resource/day9/input1")).unwrap();
-    println!("{}", mirage_maintenance::extrapolate_backwards(input));
+    match mirage_maintenance::extrapolate_backwards(input) {
+        Ok(extrapolation) => println!("{}", extrapolation),
+        Err(error) => println!("{}", error)
+    }
 }
\ No newline at end of file
@@ -25,11 +25,13 @@ fn main() {
     // day1::part2()
     // day2::part2()
     // day3::part1()
+    // day3::part2()
     // day4::part2()
     // day5::part1()
     // day6::part1()
     // day6::part2()
     // day7::part1()
+    // day7::part2()
     // day8::part1()
     // day8::part2()
     // day9::part1()